@@ -0,0 +1,200 @@
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::Path;
+
+use anyhow::Context;
+
+use crate::AocError;
+
+/// Inclusive rectangle `x0,y0,x1,y1` of the diagram that should end up in the image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Crop {
+    pub x0: i64,
+    pub y0: i64,
+    pub x1: i64,
+    pub y1: i64,
+}
+
+impl std::str::FromStr for Crop {
+    type Err = AocError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let numbers: Vec<i64> = s
+            .split(',')
+            .map(|n| n.trim().parse())
+            .collect::<Result<_, _>>()
+            .map_err(|_| AocError::ParseError(format!("invalid crop \"{s}\"")))?;
+        match numbers[..] {
+            [x0, y0, x1, y1] if x0 <= x1 && y0 <= y1 => Ok(Crop { x0, y0, x1, y1 }),
+            _ => Err(AocError::ParseError(format!(
+                "crop must be x0,y0,x1,y1 with x0 <= x1 and y0 <= y1, got \"{s}\""
+            ))),
+        }
+    }
+}
+
+impl Crop {
+    /// Smallest rectangle containing every touched cell of the diagram.
+    pub fn bounding_box(diagram: &HashMap<(i64, i64), i64>) -> Option<Crop> {
+        let mut keys = diagram.keys();
+        let &(x, y) = keys.next()?;
+        Some(keys.fold(
+            Crop {
+                x0: x,
+                y0: y,
+                x1: x,
+                y1: y,
+            },
+            |c, &(x, y)| Crop {
+                x0: c.x0.min(x),
+                y0: c.y0.min(y),
+                x1: c.x1.max(x),
+                y1: c.y1.max(y),
+            },
+        ))
+    }
+
+    fn width(&self) -> usize {
+        (self.x1 - self.x0 + 1) as usize
+    }
+
+    fn height(&self) -> usize {
+        (self.y1 - self.y0 + 1) as usize
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Binary grayscale (P5)
+    Pgm,
+    /// Binary color (P6)
+    Ppm,
+}
+
+impl Format {
+    pub fn from_path(path: &Path) -> Result<Format, AocError> {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("pgm") => Ok(Format::Pgm),
+            Some("ppm") => Ok(Format::Ppm),
+            _ => Err(AocError::ParseError(format!(
+                "cannot guess image format of {}, use .pgm or .ppm",
+                path.display()
+            ))),
+        }
+    }
+}
+
+/// Maps an overlap count to black -> red -> yellow -> white.
+fn heat_color(value: i64, max: i64) -> [u8; 3] {
+    if value <= 0 || max <= 0 {
+        return [0, 0, 0];
+    }
+    let t = value as f64 / max as f64 * 3.0;
+    let channel = |offset: f64| ((t - offset).clamp(0.0, 1.0) * 255.0).round() as u8;
+    [channel(0.0), channel(1.0), channel(2.0)]
+}
+
+/// Writes the overlap counts of `diagram` restricted to `crop` as a binary PGM or PPM image.
+///
+/// Intensities are scaled so that the highest count inside the crop is white.
+pub fn write(
+    diagram: &HashMap<(i64, i64), i64>,
+    crop: Crop,
+    format: Format,
+    out: &mut impl Write,
+) -> std::io::Result<()> {
+    let max = diagram
+        .iter()
+        .filter(|((x, y), _)| (crop.x0..=crop.x1).contains(x) && (crop.y0..=crop.y1).contains(y))
+        .map(|(_, &v)| v)
+        .max()
+        .unwrap_or(0);
+
+    let magic = match format {
+        Format::Pgm => "P5",
+        Format::Ppm => "P6",
+    };
+    write!(out, "{magic}\n{} {}\n255\n", crop.width(), crop.height())?;
+
+    let mut row = Vec::with_capacity(crop.width() * 3);
+    for y in crop.y0..=crop.y1 {
+        row.clear();
+        for x in crop.x0..=crop.x1 {
+            let value = diagram.get(&(x, y)).copied().unwrap_or(0);
+            match format {
+                Format::Pgm => row.push(if max > 0 {
+                    (value * 255 / max) as u8
+                } else {
+                    0
+                }),
+                Format::Ppm => row.extend(heat_color(value, max)),
+            }
+        }
+        out.write_all(&row)?;
+    }
+    Ok(())
+}
+
+pub fn write_file(
+    diagram: &HashMap<(i64, i64), i64>,
+    crop: Option<Crop>,
+    path: &Path,
+) -> anyhow::Result<()> {
+    let format = Format::from_path(path)?;
+    let crop = crop
+        .or_else(|| Crop::bounding_box(diagram))
+        .unwrap_or(Crop {
+            x0: 0,
+            y0: 0,
+            x1: 0,
+            y1: 0,
+        });
+    let file = std::fs::File::create(path)
+        .with_context(|| format!("Failed to create {}", path.display()))?;
+    let mut out = std::io::BufWriter::new(file);
+    write(diagram, crop, format, &mut out)?;
+    out.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parses_crops() {
+        assert_eq!(
+            " 1, 2,3,4".parse::<Crop>().unwrap(),
+            Crop {
+                x0: 1,
+                y0: 2,
+                x1: 3,
+                y1: 4
+            }
+        );
+        assert!("1,2,3".parse::<Crop>().is_err());
+        assert!("1,2,3,x".parse::<Crop>().is_err());
+        assert!("3,0,1,0".parse::<Crop>().is_err());
+    }
+
+    #[test]
+    fn writes_images() {
+        let diagram = HashMap::from([((0, 0), 2), ((1, 0), 1), ((1, 1), 2), ((5, 5), 9)]);
+        let crop = Crop {
+            x0: 0,
+            y0: 0,
+            x1: 1,
+            y1: 1,
+        };
+
+        let mut pgm = Vec::new();
+        write(&diagram, crop, Format::Pgm, &mut pgm).unwrap();
+        assert_eq!(pgm, b"P5\n2 2\n255\n\xff\x7f\x00\xff");
+
+        let mut ppm = Vec::new();
+        write(&diagram, crop, Format::Ppm, &mut ppm).unwrap();
+        let (header, pixels) = ppm.split_at(11);
+        assert_eq!(header, b"P6\n2 2\n255\n");
+        assert_eq!(pixels, [255, 255, 255, 255, 128, 0, 0, 0, 0, 255, 255, 255]);
+    }
+}
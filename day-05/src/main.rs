@@ -1,6 +1,7 @@
-#![feature(bool_to_option)]
+mod heatmap;
 
 use std::collections::HashMap;
+use std::path::PathBuf;
 
 use anyhow::Context;
 
@@ -13,7 +14,32 @@ pub enum AocError {
 }
 
 fn main() -> anyhow::Result<()> {
-    let file = std::env::args().nth(1).ok_or(AocError::NoInputFile)?;
+    let mut args = std::env::args().skip(1);
+    let file = args.next().ok_or(AocError::NoInputFile)?;
+
+    // Optional: --heatmap <out.pgm|out.ppm> [--crop x0,y0,x1,y1] [--straight-only]
+    let mut heatmap_path = None;
+    let mut crop = None;
+    let mut straight_only = false;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--heatmap" => {
+                heatmap_path = Some(PathBuf::from(args.next().ok_or_else(|| {
+                    AocError::ParseError("--heatmap needs an output path".to_owned())
+                })?))
+            }
+            "--crop" => {
+                crop = Some(
+                    args.next()
+                        .ok_or_else(|| AocError::ParseError("--crop needs x0,y0,x1,y1".to_owned()))?
+                        .parse::<heatmap::Crop>()?,
+                )
+            }
+            "--straight-only" => straight_only = true,
+            _ => return Err(AocError::ParseError(format!("unknown argument {arg}")).into()),
+        }
+    }
+
     let input = std::fs::read_to_string(file).context("Failed to read input file")?;
 
     let re = regex::Regex::new(r"(\d+),(\d+) -> (\d+),(\d+)").unwrap();
@@ -64,6 +90,11 @@ fn main() -> anyhow::Result<()> {
     let part2 = diagram2.values().filter(|&&v| v >= 2).count();
     dbg!(&part2);
 
+    if let Some(path) = heatmap_path {
+        let diagram = if straight_only { &diagram } else { &diagram2 };
+        heatmap::write_file(diagram, crop, &path)?;
+    }
+
     // Should have done it with https://en.wikipedia.org/wiki/Bresenham%27s_line_algorithm as I
     // first thought
 
@@ -0,0 +1,176 @@
+//! Tiny arithmetic language for user-defined fuel costs, e.g. `d*d + 2*d` or `abs(p - x)^3`.
+//!
+//! Variables: `d` distance, `p` crab position, `x` target position. Operators: `+ - * / % ^`,
+//! parentheses and `abs(..)`. Arithmetic saturates, so overflowing costs are effectively infinite.
+
+use crate::AocError;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    Number(i64),
+    Distance,
+    Crab,
+    Target,
+    Neg(Box<Expr>),
+    Abs(Box<Expr>),
+    Binary(char, Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    pub fn eval(&self, p: i64, x: i64) -> i64 {
+        match self {
+            Expr::Number(n) => *n,
+            Expr::Distance => (p - x).saturating_abs(),
+            Expr::Crab => p,
+            Expr::Target => x,
+            Expr::Neg(e) => e.eval(p, x).saturating_neg(),
+            Expr::Abs(e) => e.eval(p, x).saturating_abs(),
+            Expr::Binary(op, a, b) => {
+                let (a, b) = (a.eval(p, x), b.eval(p, x));
+                match op {
+                    '+' => a.saturating_add(b),
+                    '-' => a.saturating_sub(b),
+                    '*' => a.saturating_mul(b),
+                    '/' => a.checked_div(b).unwrap_or(i64::MAX),
+                    '%' => a.checked_rem(b).unwrap_or(i64::MAX),
+                    '^' => a.saturating_pow(b.clamp(0, u32::MAX as i64) as u32),
+                    _ => unreachable!(),
+                }
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for Expr {
+    type Err = AocError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let tokens: Vec<char> = s.chars().collect();
+        let mut parser = Parser {
+            tokens: &tokens,
+            pos: 0,
+        };
+        let expr = parser.sum()?;
+        match parser.peek() {
+            None => Ok(expr),
+            Some(c) => Err(parser.error(&format!("unexpected '{c}'"))),
+        }
+    }
+}
+
+struct Parser<'a> {
+    tokens: &'a [char],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    /// Next non-whitespace character
+    fn peek(&mut self) -> Option<char> {
+        while self.current().is_some_and(char::is_whitespace) {
+            self.pos += 1;
+        }
+        self.current()
+    }
+
+    fn current(&self) -> Option<char> {
+        self.tokens.get(self.pos).copied()
+    }
+
+    fn error(&self, message: &str) -> AocError {
+        AocError::ParseError(format!(
+            "{message} at position {} of cost expression",
+            self.pos
+        ))
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), AocError> {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{c}'")))
+        }
+    }
+
+    fn sum(&mut self) -> Result<Expr, AocError> {
+        let mut lhs = self.product()?;
+        while let Some(op @ ('+' | '-')) = self.peek() {
+            self.pos += 1;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(self.product()?));
+        }
+        Ok(lhs)
+    }
+
+    fn product(&mut self) -> Result<Expr, AocError> {
+        let mut lhs = self.power()?;
+        while let Some(op @ ('*' | '/' | '%')) = self.peek() {
+            self.pos += 1;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(self.power()?));
+        }
+        Ok(lhs)
+    }
+
+    fn power(&mut self) -> Result<Expr, AocError> {
+        let base = self.unary()?;
+        if self.peek() == Some('^') {
+            self.pos += 1;
+            // right associative
+            return Ok(Expr::Binary('^', Box::new(base), Box::new(self.power()?)));
+        }
+        Ok(base)
+    }
+
+    fn unary(&mut self) -> Result<Expr, AocError> {
+        if self.peek() == Some('-') {
+            self.pos += 1;
+            return Ok(Expr::Neg(Box::new(self.unary()?)));
+        }
+        self.atom()
+    }
+
+    fn atom(&mut self) -> Result<Expr, AocError> {
+        match self.peek() {
+            Some('(') => {
+                self.pos += 1;
+                let expr = self.sum()?;
+                self.expect(')')?;
+                Ok(expr)
+            }
+            Some(c) if c.is_ascii_digit() => {
+                let start = self.pos;
+                while self.current().is_some_and(|c| c.is_ascii_digit()) {
+                    self.pos += 1;
+                }
+                let digits: String = self.tokens[start..self.pos].iter().collect();
+                digits
+                    .parse()
+                    .map(Expr::Number)
+                    .map_err(|_| self.error("number too large"))
+            }
+            Some(c) if c.is_ascii_alphabetic() => {
+                let start = self.pos;
+                while self.current().is_some_and(|c| c.is_ascii_alphabetic()) {
+                    self.pos += 1;
+                }
+                let name: String = self.tokens[start..self.pos].iter().collect();
+                match name.as_str() {
+                    "d" => Ok(Expr::Distance),
+                    "p" => Ok(Expr::Crab),
+                    "x" => Ok(Expr::Target),
+                    "abs" => {
+                        self.expect('(')?;
+                        let expr = self.sum()?;
+                        self.expect(')')?;
+                        Ok(Expr::Abs(Box::new(expr)))
+                    }
+                    _ => {
+                        self.pos = start;
+                        Err(self.error(&format!("unknown name \"{name}\"")))
+                    }
+                }
+            }
+            Some(c) => Err(self.error(&format!("unexpected '{c}'"))),
+            None => Err(self.error("unexpected end")),
+        }
+    }
+}
@@ -1,6 +1,9 @@
-#![feature(bool_to_option)]
+mod expr;
+
+use std::ops::RangeInclusive;
 
 use anyhow::Context;
+use expr::Expr;
 use itertools::Itertools;
 
 #[derive(thiserror::Error, Debug)]
@@ -11,10 +14,241 @@ pub enum AocError {
     ParseError(String),
 }
 
+/// Fuel a crab at `p` needs to move to `pos`.
+///
+/// Like [`Expr`] arithmetic, fuel saturates, so overflowing costs are effectively infinite.
+#[derive(Debug, Clone)]
+enum Cost {
+    /// One unit of fuel per step, minimized by the median
+    Linear,
+    /// `n`-th step costs `n`, minimized within ½ of the mean
+    Triangular,
+    /// Squared distance, minimized within ½ of the mean
+    Quadratic,
+    /// User-defined, not necessarily convex, so all positions are tried
+    Expression(Expr),
+    /// User-defined and declared convex, so the slope search applies
+    ConvexExpression(Expr),
+}
+
+impl std::str::FromStr for Cost {
+    type Err = AocError;
+
+    /// One of the named costs or an [`Expr`], prefixed with `convex:` if it is convex in `pos`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "linear" => Cost::Linear,
+            "triangular" => Cost::Triangular,
+            "quadratic" => Cost::Quadratic,
+            _ => match s.strip_prefix("convex:") {
+                Some(e) => Cost::ConvexExpression(e.parse()?),
+                None => Cost::Expression(s.parse()?),
+            },
+        })
+    }
+}
+
+impl Cost {
+    /// Named costs are computed in `i128`, as squares of `i64` distances overflow `i64`
+    fn fuel(&self, p: i64, pos: i64) -> i128 {
+        let n = (i128::from(pos) - i128::from(p)).abs();
+        match self {
+            Cost::Linear => n,
+            // Halve the even factor first, so only a sum too large for any total saturates
+            Cost::Triangular if n % 2 == 0 => (n / 2).saturating_mul(n + 1),
+            Cost::Triangular => n.saturating_mul((n + 1) / 2),
+            Cost::Quadratic => n.saturating_mul(n),
+            Cost::Expression(e) | Cost::ConvexExpression(e) => i128::from(e.eval(p, pos)),
+        }
+    }
+
+    fn is_convex(&self) -> bool {
+        !matches!(self, Cost::Expression(_))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Alignment {
+    /// All positions with minimal fuel, as ascending ranges
+    positions: Vec<RangeInclusive<i64>>,
+    fuel: i128,
+}
+
+fn total_fuel(crabs: &[i64], cost: &Cost, pos: i64) -> i128 {
+    crabs
+        .iter()
+        .fold(0, |sum: i128, &p| sum.saturating_add(cost.fuel(p, pos)))
+}
+
+/// A local minimum of a convex function is a global one, so `pos` is optimal if neither
+/// neighbor is cheaper.
+fn is_optimal(crabs: &[i64], cost: &Cost, (position, fuel): (i64, i128)) -> bool {
+    total_fuel(crabs, cost, position) == fuel
+        && total_fuel(crabs, cost, position - 1) >= fuel
+        && total_fuel(crabs, cost, position + 1) >= fuel
+}
+
+fn median(crabs: &[i64]) -> i64 {
+    let mut crabs = crabs.to_vec();
+    let mid = crabs.len() / 2;
+    *crabs.select_nth_unstable(mid).1
+}
+
+/// The derivative of the triangular total is `Σ(pos - p) + Σsign(pos - p) / 2`, which vanishes
+/// within ½ of the mean.
+fn mean_candidates(crabs: &[i64]) -> impl Iterator<Item = i64> {
+    let n = crabs.len() as i128;
+    let sum: i128 = crabs.iter().map(|&p| p as i128).sum();
+    let floor = sum.div_euclid(n) as i64;
+    floor - 1..=floor + 2
+}
+
+/// Smallest position in `min..=max` where the convex total stops decreasing.
+fn slope_search(crabs: &[i64], cost: &Cost, mut min: i64, mut max: i64) -> (i64, i128) {
+    while min < max {
+        let mid = min + (max - min) / 2;
+        if total_fuel(crabs, cost, mid + 1) < total_fuel(crabs, cost, mid) {
+            min = mid + 1;
+        } else {
+            max = mid;
+        }
+    }
+    (min, total_fuel(crabs, cost, min))
+}
+
+/// First position in `min..=to` that already costs `fuel`, the total being non-increasing there.
+fn plateau_start(crabs: &[i64], cost: &Cost, fuel: i128, mut min: i64, mut to: i64) -> i64 {
+    while min < to {
+        let mid = min + (to - min) / 2;
+        if total_fuel(crabs, cost, mid) == fuel {
+            to = mid;
+        } else {
+            min = mid + 1;
+        }
+    }
+    to
+}
+
+/// Last position in `from..=max` that still costs `fuel`, the total being non-decreasing there.
+fn plateau_end(crabs: &[i64], cost: &Cost, fuel: i128, mut from: i64, mut max: i64) -> i64 {
+    while from < max {
+        let mid = from + (max - from + 1) / 2;
+        if total_fuel(crabs, cost, mid) == fuel {
+            from = mid;
+        } else {
+            max = mid - 1;
+        }
+    }
+    from
+}
+
+/// Tries every position, for costs without known structure.
+fn exhaustive(crabs: &[i64], cost: &Cost, min: i64, max: i64) -> Alignment {
+    let mut alignment = Alignment {
+        positions: Vec::new(),
+        fuel: i128::MAX,
+    };
+    for pos in min..=max {
+        let fuel = total_fuel(crabs, cost, pos);
+        if fuel < alignment.fuel {
+            alignment.fuel = fuel;
+            alignment.positions.clear();
+        }
+        if fuel == alignment.fuel {
+            match alignment.positions.last_mut() {
+                Some(range) if *range.end() == pos - 1 => *range = *range.start()..=pos,
+                _ => alignment.positions.push(pos..=pos),
+            }
+        }
+    }
+    alignment
+}
+
+fn align(crabs: &[i64], cost: &Cost) -> Option<Alignment> {
+    let min = *crabs.iter().min()?;
+    let max = *crabs.iter().max()?;
+
+    if !cost.is_convex() {
+        return Some(exhaustive(crabs, cost, min, max));
+    }
+
+    let candidate = match cost {
+        Cost::Linear => Some(median(crabs)),
+        Cost::Triangular | Cost::Quadratic => {
+            mean_candidates(crabs).min_by_key(|&pos| (total_fuel(crabs, cost, pos), pos))
+        }
+        Cost::Expression(_) | Cost::ConvexExpression(_) => None,
+    }
+    .map(|pos| (pos, total_fuel(crabs, cost, pos)));
+
+    let (position, fuel) = match candidate {
+        Some(candidate) if is_optimal(crabs, cost, candidate) => candidate,
+        _ => slope_search(crabs, cost, min, max),
+    };
+    // Optimal positions of a convex total are contiguous, widen to both sides
+    let first = plateau_start(crabs, cost, fuel, min, position);
+    let last = plateau_end(crabs, cost, fuel, position, max);
+    Some(Alignment {
+        positions: vec![first..=last],
+        fuel,
+    })
+}
+
+fn format_positions(positions: &[RangeInclusive<i64>]) -> String {
+    positions
+        .iter()
+        .map(|r| {
+            if r.start() == r.end() {
+                r.start().to_string()
+            } else {
+                format!("{}..={}", r.start(), r.end())
+            }
+        })
+        .join(", ")
+}
+
+fn report(crabs: &[i64], name: &str, cost: &Cost, contributions: bool) {
+    let alignment = match align(crabs, cost) {
+        Some(alignment) => alignment,
+        None => return,
+    };
+    println!("cost: {name}");
+    println!(
+        "optimal position(s): {}",
+        format_positions(&alignment.positions)
+    );
+    println!("total fuel: {}", alignment.fuel);
+    if contributions {
+        let position = *alignment.positions[0].start();
+        println!("crab,position,fuel");
+        for (i, &p) in crabs.iter().enumerate() {
+            println!("{i},{p},{}", cost.fuel(p, position));
+        }
+    }
+}
+
 fn main() -> anyhow::Result<()> {
-    let file = std::env::args().nth(1).ok_or(AocError::NoInputFile)?;
+    let mut args = std::env::args().skip(1);
+    let file = args.next().ok_or(AocError::NoInputFile)?;
     let input = std::fs::read_to_string(file).context("Failed to read input file")?;
 
+    // Optional: --cost <linear|triangular|quadratic|EXPR|convex:EXPR>... [--contributions]
+    let mut costs = Vec::new();
+    let mut contributions = false;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--cost" => {
+                let name = args.next().ok_or_else(|| {
+                    AocError::ParseError("--cost needs a name or expression".to_owned())
+                })?;
+                let cost: Cost = name.parse()?;
+                costs.push((name, cost));
+            }
+            "--contributions" => contributions = true,
+            _ => return Err(AocError::ParseError(format!("unknown argument {arg}")).into()),
+        }
+    }
+
     let input: Vec<i64> = input
         .lines()
         .next()
@@ -22,25 +256,120 @@ fn main() -> anyhow::Result<()> {
         .split(',')
         .flat_map(|n| n.parse())
         .collect();
+    if input.is_empty() {
+        return Err(AocError::ParseError("No numbers in input".to_string()).into());
+    }
 
-    let (&min, &max) = input
-        .iter()
-        .minmax()
-        .into_option()
-        .ok_or(AocError::ParseError("No numbers in input".to_string()))?;
-
-    let calc_fuel = |f: fn(i64, i64) -> i64| {
-        (min..=max).min_by_key(|&pos| input.iter().map(|&p| f(p, pos)).sum::<i64>())
-    };
+    if costs.is_empty() {
+        let part1 = align(&input, &Cost::Linear);
+        dbg!(&part1);
 
-    let part1 = calc_fuel(|p, pos| (p - pos).abs());
-    dbg!(&part1);
+        let part2 = align(&input, &Cost::Triangular);
+        dbg!(&part2);
+    }
 
-    let part2 = calc_fuel(|p, pos| {
-        let n = (pos - p).abs();
-        n * (n + 1) / 2
-    });
-    dbg!(&part2);
+    for (i, (name, cost)) in costs.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        report(&input, name, cost, contributions);
+    }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn brute_force(crabs: &[i64], cost: &Cost) -> Alignment {
+        let (min, max) = (*crabs.iter().min().unwrap(), *crabs.iter().max().unwrap());
+        exhaustive(crabs, cost, min, max)
+    }
+
+    #[test]
+    fn matches_brute_force() {
+        let example = [16, 1, 2, 0, 4, 2, 7, 1, 2, 14];
+        assert_eq!(
+            align(&example, &Cost::Linear),
+            Some(Alignment {
+                positions: vec![2..=2],
+                fuel: 37
+            })
+        );
+        assert_eq!(
+            align(&example, &Cost::Triangular),
+            Some(Alignment {
+                positions: vec![5..=5],
+                fuel: 168
+            })
+        );
+
+        for crabs in [
+            &[-7, 3, 3, 100, 41, 0, 2, 2, 999, -50, 12][..],
+            &[0, 10, 3, 7],
+            &[1, 1, 5, 5],
+        ] {
+            for cost in ["linear", "triangular", "quadratic", "d^3 + d"] {
+                let cost: Cost = cost.parse().unwrap();
+                assert_eq!(align(crabs, &cost), Some(brute_force(crabs, &cost)));
+            }
+        }
+
+        // Too wide to try every position, and too costly for `i64` fuel per crab
+        let crabs = [0, 10_000_000_000, 5];
+        for (cost, position, fuel) in [
+            (Cost::Linear, 5, 10_000_000_000),
+            (Cost::Triangular, 3333333335, 33333333323333333340),
+            (Cost::Quadratic, 3333333335, 66666666633333333350),
+        ] {
+            let alignment = align(&crabs, &cost).unwrap();
+            assert_eq!(alignment.positions, vec![position..=position]);
+            assert_eq!(alignment.fuel, fuel);
+            assert_eq!(
+                exhaustive(&crabs, &cost, position - 1000, position + 1000),
+                alignment
+            );
+        }
+    }
+
+    #[test]
+    fn expression_costs() {
+        let cost: Cost = "d * (d + 1) / 2".parse().unwrap();
+        assert_eq!(
+            align(&[16, 1, 2, 0, 4, 2, 7, 1, 2, 14], &cost)
+                .unwrap()
+                .fuel,
+            168
+        );
+
+        // Not convex: cheapest far away from everyone
+        let cost: Cost = "-d * d".parse().unwrap();
+        assert_eq!(align(&[0, 9], &cost).unwrap().positions, vec![0..=0, 9..=9]);
+
+        // Declared convex: the slope search finds the same optimum as the closed form, on a
+        // range far too wide to try every position
+        let crabs = [0, 3, 2_000_000_000];
+        let cost: Cost = "convex:d * d".parse().unwrap();
+        assert!(matches!(cost, Cost::ConvexExpression(_)));
+        let expected = align(&crabs, &Cost::Quadratic);
+        assert_eq!(
+            expected.as_ref().unwrap().positions,
+            vec![666666668..=666666668]
+        );
+        assert_eq!(align(&crabs, &cost), expected);
+        assert_eq!(
+            slope_search(&crabs, &cost, 0, 2_000_000_000),
+            (666666668, expected.unwrap().fuel)
+        );
+
+        // Named costs saturate like expressions instead of wrapping around
+        assert_eq!(Cost::Quadratic.fuel(i64::MIN, i64::MAX), i128::MAX);
+        let crabs = [i64::MIN, i64::MAX, i64::MAX];
+        assert_eq!(total_fuel(&crabs, &Cost::Triangular, i64::MIN), i128::MAX);
+
+        assert!("d +".parse::<Cost>().is_err());
+        assert!("convex:".parse::<Cost>().is_err());
+        assert!("abs(p - y)".parse::<Cost>().is_err());
+    }
+}
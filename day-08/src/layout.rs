@@ -0,0 +1,277 @@
+use std::collections::HashMap;
+
+use itertools::Itertools;
+
+use crate::AocError;
+
+/// Set of wires or segments, bit `i` standing for the `i`-th segment name
+pub type Segments = u32;
+
+/// Segment names and the glyphs a display can show.
+///
+/// Definitions are plain text: `segments <names>` followed by one `<label> <segments>` line per
+/// glyph, `#` starts a comment. See `layouts/` for examples.
+#[derive(Debug, Clone)]
+pub struct Layout {
+    names: Vec<char>,
+    glyphs: Vec<(String, Segments)>,
+}
+
+impl std::str::FromStr for Layout {
+    type Err = AocError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = s
+            .lines()
+            .map(|l| l.split('#').next().unwrap_or_default().trim())
+            .filter(|l| !l.is_empty());
+
+        let names: Vec<char> = match lines.next().and_then(|l| l.strip_prefix("segments")) {
+            Some(names) => names.chars().filter(|c| !c.is_whitespace()).collect(),
+            None => {
+                return Err(AocError::ParseError(
+                    "layout must start with \"segments <names>\"".to_owned(),
+                ))
+            }
+        };
+        if names.is_empty() || names.len() > Segments::BITS as usize || !names.iter().all_unique() {
+            return Err(AocError::ParseError(format!(
+                "layout needs 1 to {} distinct segment names",
+                Segments::BITS
+            )));
+        }
+
+        let mut layout = Layout {
+            names,
+            glyphs: Vec::new(),
+        };
+        for line in lines {
+            let (label, segments) = line
+                .split_whitespace()
+                .collect_tuple()
+                .ok_or_else(|| AocError::ParseError(format!("invalid glyph line \"{line}\"")))?;
+            let segments = layout.parse_pattern(segments).ok_or_else(|| {
+                AocError::ParseError(format!("unknown segment in glyph \"{label}\""))
+            })?;
+            if let Some((other, _)) = layout.glyphs.iter().find(|(_, g)| *g == segments) {
+                return Err(AocError::ParseError(format!(
+                    "glyphs \"{other}\" and \"{label}\" look the same"
+                )));
+            }
+            layout.glyphs.push((label.to_owned(), segments));
+        }
+        Ok(layout)
+    }
+}
+
+impl Layout {
+    pub fn seven_segment() -> Layout {
+        include_str!("../layouts/seven-segment")
+            .parse()
+            .expect("built-in layout is valid")
+    }
+
+    pub fn parse_pattern(&self, word: &str) -> Option<Segments> {
+        word.chars().try_fold(0, |mask, c| {
+            let index = self.names.iter().position(|&n| n == c)?;
+            Some(mask | 1 << index)
+        })
+    }
+
+    pub fn segment_name(&self, segment: usize) -> char {
+        self.names[segment]
+    }
+
+    /// Glyph shown by `segments` once rewired
+    pub fn label(&self, segments: Segments) -> Option<&str> {
+        self.glyphs
+            .iter()
+            .find(|(_, g)| *g == segments)
+            .map(|(label, _)| label.as_str())
+    }
+
+    /// Glyphs that are identified by their number of lit segments alone
+    pub fn unique_sizes(&self) -> Vec<u32> {
+        self.glyphs
+            .iter()
+            .map(|(_, g)| g.count_ones())
+            .counts()
+            .into_iter()
+            .filter(|&(_, count)| count == 1)
+            .map(|(size, _)| size)
+            .collect()
+    }
+
+    fn sizes_containing(bit: usize, patterns: impl Iterator<Item = Segments>) -> Vec<u32> {
+        patterns
+            .filter(|&p| p & 1 << bit != 0)
+            .map(|p| p.count_ones())
+            .sorted()
+            .collect()
+    }
+
+    /// Segments each wire could drive. If every glyph has been observed, a wire and its segment
+    /// are lit in patterns of the same sizes; for the seven-segment digits this alone combines
+    /// how often a segment is lit with which of `1`, `4`, `7` contain it and settles the wiring.
+    fn candidates(&self, patterns: &[Segments]) -> Vec<Segments> {
+        let n = self.names.len();
+        let all: Segments = if n == 32 { !0 } else { (1 << n) - 1 };
+        if patterns.len() != self.glyphs.len() {
+            return vec![all; n];
+        }
+        let segment_signatures: Vec<Vec<u32>> = (0..n)
+            .map(|s| Self::sizes_containing(s, self.glyphs.iter().map(|&(_, g)| g)))
+            .collect();
+        (0..n)
+            .map(|w| {
+                let signature = Self::sizes_containing(w, patterns.iter().copied());
+                (0..n)
+                    .filter(|&s| segment_signatures[s] == signature)
+                    .fold(0, |mask, s| mask | 1 << s)
+            })
+            .collect()
+    }
+
+    /// Up to `limit` wirings (`wiring[wire] == segment`) that turn every pattern into a
+    /// different glyph.
+    pub fn wirings(&self, patterns: &[Segments], limit: usize) -> Vec<Vec<usize>> {
+        let patterns: Vec<Segments> = patterns.iter().copied().unique().collect();
+        let mut search = Search {
+            patterns: &patterns,
+            glyphs_by_size: self
+                .glyphs
+                .iter()
+                .map(|&(_, g)| (g.count_ones(), g))
+                .into_group_map(),
+            candidates: self.candidates(&patterns),
+            wiring: Vec::new(),
+            used: 0,
+            found: Vec::new(),
+            limit,
+        };
+        search.run();
+        search.found
+    }
+}
+
+pub fn rewire(pattern: Segments, wiring: &[usize]) -> Segments {
+    wiring
+        .iter()
+        .enumerate()
+        .filter(|(wire, _)| pattern & 1 << wire != 0)
+        .fold(0, |mask, (_, &segment)| mask | 1 << segment)
+}
+
+/// Backtracking over wire assignments, pruned by requiring each pattern to still fit a glyph
+struct Search<'a> {
+    patterns: &'a [Segments],
+    glyphs_by_size: HashMap<u32, Vec<Segments>>,
+    candidates: Vec<Segments>,
+    wiring: Vec<usize>,
+    used: Segments,
+    found: Vec<Vec<usize>>,
+    limit: usize,
+}
+
+impl Search<'_> {
+    fn consistent(&self) -> bool {
+        // `rewire` only looks at the wires assigned so far
+        self.patterns.iter().all(|&p| {
+            let inside = rewire(p, &self.wiring);
+            let outside = rewire(!p, &self.wiring);
+            self.glyphs_by_size
+                .get(&p.count_ones())
+                .is_some_and(|glyphs| {
+                    glyphs
+                        .iter()
+                        .any(|&g| g & inside == inside && g & outside == 0)
+                })
+        })
+    }
+
+    fn run(&mut self) {
+        if self.found.len() >= self.limit {
+            return;
+        }
+        let wire = self.wiring.len();
+        if wire == self.candidates.len() {
+            let images: Vec<Segments> = self
+                .patterns
+                .iter()
+                .map(|&p| rewire(p, &self.wiring))
+                .collect();
+            if images.iter().all_unique() {
+                self.found.push(self.wiring.clone());
+            }
+            return;
+        }
+        for segment in 0..self.candidates.len() {
+            let bit = 1 << segment;
+            if self.candidates[wire] & bit == 0 || self.used & bit != 0 {
+                continue;
+            }
+            self.wiring.push(segment);
+            self.used |= bit;
+            if self.consistent() {
+                self.run();
+            }
+            self.used &= !bit;
+            self.wiring.pop();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn wirings(layout: &Layout, patterns: &str) -> Vec<String> {
+        let patterns: Vec<Segments> = patterns
+            .split(' ')
+            .flat_map(|p| layout.parse_pattern(p))
+            .collect();
+        layout
+            .wirings(&patterns, 2)
+            .iter()
+            .map(|w| w.iter().map(|&s| layout.names[s]).collect())
+            .collect()
+    }
+
+    #[test]
+    fn deduces_example_wiring() {
+        let layout = Layout::seven_segment();
+        // a->c, b->f, c->g, d->a, e->b, f->d, g->e
+        assert_eq!(
+            wirings(
+                &layout,
+                "acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb cagedb ab"
+            ),
+            ["cfgabde"]
+        );
+        assert_eq!(wirings(&layout, "cdfbe gcdfa fbcad dab cefabd").len(), 2);
+        assert!(wirings(&layout, "abc abd").is_empty());
+    }
+
+    #[test]
+    fn unscrambles_other_layouts() {
+        for definition in [
+            include_str!("../layouts/fourteen-segment"),
+            include_str!("../layouts/sixteen-segment"),
+        ] {
+            let layout: Layout = definition.parse().unwrap();
+            let n = layout.names.len();
+            // wire i drives segment (5 * i + 3) % n
+            let wiring: Vec<usize> = (0..n).map(|i| (5 * i + 3) % n).collect();
+            let mut inverse = vec![0; n];
+            for (wire, &segment) in wiring.iter().enumerate() {
+                inverse[segment] = wire;
+            }
+            let patterns: Vec<Segments> = layout
+                .glyphs
+                .iter()
+                .map(|&(_, g)| rewire(g, &inverse))
+                .collect();
+            assert_eq!(layout.wirings(&patterns, 2), [wiring]);
+        }
+    }
+}
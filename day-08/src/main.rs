@@ -1,9 +1,8 @@
-#![feature(bool_to_option)]
-
-use std::collections::HashMap;
+mod layout;
 
 use anyhow::Context;
 use itertools::Itertools;
+use layout::{rewire, Layout, Segments};
 
 #[derive(thiserror::Error, Debug)]
 pub enum AocError {
@@ -11,20 +10,35 @@ pub enum AocError {
     NoInputFile,
     #[error("Failed to parse: {0}")]
     ParseError(String),
-}
-
-fn decode(word: &str, a: &Vec<char>, b: &Vec<char>) -> String {
-    word.chars()
-        .map(|c| b.get(a.iter().position(|x| c == *x).unwrap()).unwrap())
-        .copied()
-        .sorted()
-        .collect()
+    #[error("Could not deduce the wiring of line {0}")]
+    Undecodable(usize),
+    #[error("Wiring of line {0} is ambiguous under this layout")]
+    Ambiguous(usize),
 }
 
 fn main() -> anyhow::Result<()> {
-    let file = std::env::args().nth(1).ok_or(AocError::NoInputFile)?;
+    let mut args = std::env::args().skip(1);
+    let file = args.next().ok_or(AocError::NoInputFile)?;
     let input = std::fs::read_to_string(file).context("Failed to read input file")?;
 
+    // Optional: --layout <definition file> (default: seven-segment digits), --wiring
+    let mut layout = Layout::seven_segment();
+    let mut show_wiring = false;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--layout" => {
+                let path = args.next().ok_or_else(|| {
+                    AocError::ParseError("--layout needs a definition file".to_owned())
+                })?;
+                layout = std::fs::read_to_string(&path)
+                    .with_context(|| format!("Failed to read layout {path}"))?
+                    .parse()?;
+            }
+            "--wiring" => show_wiring = true,
+            _ => return Err(AocError::ParseError(format!("unknown argument {arg}")).into()),
+        }
+    }
+
     let input: Vec<Vec<Vec<&str>>> = input
         .lines()
         .map(|l| {
@@ -34,61 +48,78 @@ fn main() -> anyhow::Result<()> {
         })
         .collect();
 
+    let unique_sizes = layout.unique_sizes();
     let part1: usize = input
         .iter()
         .map(|l| {
-            l[1].iter()
-                .filter(|c| match c.len() {
-                    2 | 3 | 4 | 7 => true,
-                    _ => false,
-                })
-                .count()
+            l.get(1).map_or(0, |output| {
+                output
+                    .iter()
+                    .filter(|c| unique_sizes.contains(&(c.chars().count() as u32)))
+                    .count()
+            })
         })
         .sum();
     dbg!(&part1);
 
-    let decode_table: HashMap<&str, usize> = [
-        ("abcefg", 0),
-        ("cf", 1),
-        ("acdeg", 2),
-        ("acdfg", 3),
-        ("bcdf", 4),
-        ("abdfg", 5),
-        ("abdefg", 6),
-        ("acf", 7),
-        ("abcdefg", 8),
-        ("abcdfg", 9),
-    ]
-    .iter()
-    .copied()
-    .collect();
-
-    let char_vec: Vec<char> = "abcdefg".chars().collect();
+    let mut decoded = Vec::new();
+    for (line, l) in input.iter().enumerate() {
+        let parse = |words: &[&str]| -> Result<Vec<Segments>, AocError> {
+            words
+                .iter()
+                .map(|w| {
+                    layout
+                        .parse_pattern(w)
+                        .ok_or_else(|| AocError::ParseError(format!("invalid pattern \"{w}\"")))
+                })
+                .collect()
+        };
+        let (patterns, output) = match &l[..] {
+            [patterns, output] => (parse(patterns)?, parse(output)?),
+            _ => return Err(AocError::ParseError(format!("line {} lacks '|'", line + 1)).into()),
+        };
 
-    let part2: usize = input
-        .iter()
-        .flat_map(|l| {
-            "abcdefg"
-                .chars()
-                .permutations(7)
-                .flat_map(|p| {
-                    for word in l[0].iter() {
-                        decode_table.get(decode(word, &p, &char_vec).as_str())?;
-                    }
+        // Output patterns constrain the wiring just like the others
+        let observed = patterns.iter().chain(output.iter()).copied().collect_vec();
+        let wiring = match &layout.wirings(&observed, 2)[..] {
+            [wiring] => wiring.clone(),
+            [] => return Err(AocError::Undecodable(line + 1).into()),
+            _ => return Err(AocError::Ambiguous(line + 1).into()),
+        };
+        if show_wiring {
+            println!(
+                "{}",
+                wiring
+                    .iter()
+                    .enumerate()
+                    .map(|(w, &s)| format!(
+                        "{}->{}",
+                        layout.segment_name(w),
+                        layout.segment_name(s)
+                    ))
+                    .join(" ")
+            );
+        }
 
-                    let mut number = 0;
-                    for word in l[1].iter() {
-                        number *= 10;
-                        let digit = decode_table.get(decode(word, &p, &char_vec).as_str())?;
-                        number += digit;
-                    }
-                    Some(number)
-                })
-                .next()
-        })
-        .sum();
+        let labels = output
+            .iter()
+            .map(|&p| layout.label(rewire(p, &wiring)))
+            .collect::<Option<Vec<&str>>>()
+            .ok_or(AocError::Undecodable(line + 1))?;
+        decoded.push(labels.concat());
+    }
 
-    dbg!(&part2);
+    match decoded
+        .iter()
+        .map(|d| d.parse::<usize>())
+        .collect::<Result<Vec<_>, _>>()
+    {
+        Ok(numbers) => {
+            let part2: usize = numbers.iter().sum();
+            dbg!(&part2);
+        }
+        Err(_) => decoded.iter().for_each(|d| println!("{d}")),
+    }
 
     Ok(())
 }
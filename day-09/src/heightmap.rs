@@ -0,0 +1,106 @@
+use crate::AocError;
+
+/// Heights on a regular N-dimensional grid, stored flat with x varying fastest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Heightmap {
+    /// Extent along x, y, z, ...
+    pub dims: Vec<usize>,
+    pub heights: Vec<i64>,
+}
+
+impl std::str::FromStr for Heightmap {
+    type Err = AocError;
+
+    /// Rows of digits form a 2D map; several such layers separated by blank lines are stacked
+    /// along z.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut layers: Vec<Vec<Vec<i64>>> = vec![Vec::new()];
+        for line in s.lines().map(str::trim) {
+            let layer = layers.last_mut().unwrap();
+            if line.is_empty() {
+                if !layer.is_empty() {
+                    layers.push(Vec::new());
+                }
+                continue;
+            }
+            let row = line
+                .chars()
+                .map(|c| c.to_digit(10).map(i64::from))
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(|| AocError::ParseError(format!("non-digit in \"{line}\"")))?;
+            layer.push(row);
+        }
+        if layers.last().is_some_and(Vec::is_empty) {
+            layers.pop();
+        }
+
+        let height = layers.first().map_or(0, Vec::len);
+        let width = layers.first().and_then(|l| l.first()).map_or(0, Vec::len);
+        if width == 0 {
+            return Err(AocError::ParseError("empty heightmap".to_owned()));
+        }
+        if layers
+            .iter()
+            .any(|l| l.len() != height || l.iter().any(|row| row.len() != width))
+        {
+            return Err(AocError::ParseError(
+                "all rows and layers must have the same size".to_owned(),
+            ));
+        }
+
+        let mut dims = vec![width, height];
+        if layers.len() > 1 {
+            dims.push(layers.len());
+        }
+        Ok(Heightmap {
+            dims,
+            heights: layers.into_iter().flatten().flatten().collect(),
+        })
+    }
+}
+
+impl Heightmap {
+    pub fn len(&self) -> usize {
+        self.heights.len()
+    }
+
+    pub fn width(&self) -> usize {
+        self.dims[0]
+    }
+
+    fn strides(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.dims.iter().scan(1, |stride, &dim| {
+            let current = *stride;
+            *stride *= dim;
+            Some((current, dim))
+        })
+    }
+
+    /// Coordinates `(x, y, z, ...)` of a flat index
+    pub fn coordinates(&self, index: usize) -> Vec<usize> {
+        self.strides()
+            .map(|(stride, dim)| index / stride % dim)
+            .collect()
+    }
+
+    /// Von Neumann neighborhood: one step along a single axis
+    pub fn neighbors(&self, index: usize) -> impl Iterator<Item = usize> + '_ {
+        self.strides().flat_map(move |(stride, dim)| {
+            let c = index / stride % dim;
+            let lower = (c > 0).then(|| index - stride);
+            let upper = (c + 1 < dim).then_some(index + stride);
+            lower.into_iter().chain(upper)
+        })
+    }
+
+    /// Rows along x of every 2D slice; `true` marks the first row of a new slice
+    pub fn rows(&self) -> impl Iterator<Item = (bool, std::ops::Range<usize>)> + '_ {
+        let rows_per_slice = self.dims.get(1).copied().unwrap_or(1);
+        (0..self.len() / self.width()).map(move |r| {
+            (
+                r > 0 && r % rows_per_slice == 0,
+                r * self.width()..(r + 1) * self.width(),
+            )
+        })
+    }
+}
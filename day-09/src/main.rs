@@ -1,6 +1,10 @@
-use std::{collections::HashMap, convert::identity};
+mod heightmap;
+mod render;
+
+use std::io::Write;
 
 use anyhow::Context;
+use heightmap::Heightmap;
 use itertools::Itertools;
 
 #[derive(thiserror::Error, Debug)]
@@ -11,71 +15,230 @@ pub enum AocError {
     ParseError(String),
 }
 
-const OFFSETS: [(i64, i64); 4] = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+/// Height that separates basins
+const WALL: i64 = 9;
 
-fn offset(array: &Vec<Vec<i64>>, (x, y): (i64, i64), (dx, dy): (i64, i64)) -> Option<&i64> {
-    array
-        .get((y + dy) as usize)
-        .and_then(|v| v.get((x + dx) as usize))
+/// Flood fill from `start` over neighbors whose height is accepted by `accept`.
+fn flood_fill(
+    map: &Heightmap,
+    start: usize,
+    accept: impl Fn(i64) -> bool,
+    visited: &mut [bool],
+) -> Vec<usize> {
+    let mut region = Vec::new();
+    let mut stack = vec![start];
+    visited[start] = true;
+    while let Some(index) = stack.pop() {
+        region.push(index);
+        for next in map.neighbors(index) {
+            if accept(map.heights[next]) && !visited[next] {
+                visited[next] = true;
+                stack.push(next);
+            }
+        }
+    }
+    region
 }
 
-fn neighbors(array: &Vec<Vec<i64>>, pos: (i64, i64)) -> [Option<&i64>; 4] {
-    OFFSETS.map(|o| offset(array, pos, o))
+#[derive(Debug, Clone, Default)]
+pub struct Basin {
+    size: usize,
+    /// Cells strictly lower than all their neighbors
+    low_points: Vec<usize>,
+    /// Regions of equal height without lower neighbors, including the low points
+    minima: Vec<Vec<usize>>,
 }
 
-fn main() -> anyhow::Result<()> {
-    let file = std::env::args().nth(1).ok_or(AocError::NoInputFile)?;
-    let input = std::fs::read_to_string(file).context("Failed to read input file")?;
+impl Basin {
+    fn has_plateau(&self) -> bool {
+        self.minima.iter().any(|m| m.len() > 1)
+    }
+}
 
-    let input: Vec<Vec<i64>> = input
-        .lines()
-        .map(|l| l.chars().flat_map(|c| format!("{}", c).parse()).collect())
-        .collect();
+#[derive(Debug)]
+pub struct Segmentation {
+    /// Basin index per cell, `None` for walls
+    labels: Vec<Option<usize>>,
+    basins: Vec<Basin>,
+}
 
-    let mut sum = 0;
-    for y in 0..input.len() {
-        for x in 0..input[0].len() {
-            let center = input[y][x];
+/// Splits the heightmap into basins, i.e. connected regions bounded by walls of height 9.
+fn segment(map: &Heightmap) -> Segmentation {
+    let mut visited = vec![false; map.len()];
+    let mut labels = vec![None; map.len()];
+    let mut basins = Vec::new();
 
-            if neighbors(&input, (x.try_into()?, y.try_into()?))
-                .iter()
-                .flat_map(identity)
-                .all(|&&n| center < n)
-            {
-                sum += 1 + center;
+    for index in 0..map.len() {
+        if map.heights[index] >= WALL || visited[index] {
+            continue;
+        }
+        let region = flood_fill(map, index, |h| h < WALL, &mut visited);
+        for &i in region.iter() {
+            labels[i] = Some(basins.len());
+        }
+        basins.push(Basin {
+            size: region.len(),
+            ..Basin::default()
+        });
+    }
+
+    let mut visited = vec![false; map.len()];
+    for index in 0..map.len() {
+        let height = map.heights[index];
+        let label = match labels[index] {
+            Some(label) if !visited[index] => label,
+            _ => continue,
+        };
+        let plateau = flood_fill(map, index, |h| h == height, &mut visited);
+        let is_minimum = plateau
+            .iter()
+            .all(|&i| map.neighbors(i).all(|n| map.heights[n] >= height));
+        if is_minimum {
+            if plateau.len() == 1 {
+                basins[label].low_points.push(plateau[0]);
             }
+            basins[label].minima.push(plateau);
         }
     }
-    dbg!(&sum);
 
-    let mut basin_scores = HashMap::new();
+    Segmentation { labels, basins }
+}
 
-    for y in 0..input.len() {
-        for x in 0..input[0].len() {
-            let mut pos = (x.try_into()?, y.try_into()?);
-            let mut center = input[y][x];
-            if center == 9 {
-                continue;
+fn main() -> anyhow::Result<()> {
+    let mut args = std::env::args().skip(1);
+    let file = args.next().ok_or(AocError::NoInputFile)?;
+
+    // Optional: --basins (label map and basin list), --render (colored map), --ppm <out.ppm>
+    let mut show_basins = false;
+    let mut render = false;
+    let mut ppm_path = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--basins" => show_basins = true,
+            "--render" => render = true,
+            "--ppm" => {
+                ppm_path =
+                    Some(args.next().ok_or_else(|| {
+                        AocError::ParseError("--ppm needs an output path".to_owned())
+                    })?)
             }
-            loop {
-                center = input[pos.1 as usize][pos.0 as usize];
-                let next = neighbors(&input, pos)
-                    .iter()
-                    .zip(OFFSETS.iter())
-                    .flat_map(|(val, pos)| val.map(|v| (v, pos)))
-                    .filter(|(val, _)| center > **val)
-                    .min_by_key(|(val, _pos)| *val);
-                if let Some((_, &(dx, dy))) = next {
-                    pos = (pos.0 + dx, pos.1 + dy);
-                } else {
-                    break;
-                }
+            _ => return Err(AocError::ParseError(format!("unknown argument {arg}")).into()),
+        }
+    }
+    let input = std::fs::read_to_string(file).context("Failed to read input file")?;
+
+    let input: Heightmap = input.parse()?;
+
+    let segmentation = segment(&input);
+
+    let sum: i64 = segmentation
+        .basins
+        .iter()
+        .flat_map(|b| b.low_points.iter())
+        .map(|&i| 1 + input.heights[i])
+        .sum();
+    dbg!(&sum);
+
+    if show_basins {
+        for (new_slice, row) in input.rows() {
+            if new_slice {
+                println!();
             }
-            *basin_scores.entry(pos).or_insert(0) += 1;
+            println!(
+                "{}",
+                segmentation.labels[row]
+                    .iter()
+                    .map(|l| l.map_or("-".to_owned(), |l| l.to_string()))
+                    .join(" ")
+            );
+        }
+    }
+    for (i, basin) in segmentation.basins.iter().enumerate() {
+        if show_basins {
+            let low_points = basin
+                .low_points
+                .iter()
+                .map(|&p| input.coordinates(p))
+                .collect_vec();
+            println!("basin {i}: size {}, low points {low_points:?}", basin.size);
+        }
+        if basin.minima.len() > 1 {
+            eprintln!("warning: basin {i} has {} local minima", basin.minima.len());
+        }
+        if basin.has_plateau() {
+            eprintln!("warning: basin {i} has a flat minimum (plateau)");
         }
     }
 
-    let part2: i64 = basin_scores.values().sorted().rev().take(3).product();
+    if render {
+        let mut stdout = std::io::stdout().lock();
+        render::ansi(&input, &segmentation, &mut stdout)?;
+    }
+    if let Some(path) = ppm_path {
+        let file =
+            std::fs::File::create(&path).with_context(|| format!("Failed to create {path}"))?;
+        let mut out = std::io::BufWriter::new(file);
+        render::ppm(&input, &segmentation, &mut out)?;
+        out.flush()?;
+    }
+
+    let part2: usize = segmentation
+        .basins
+        .iter()
+        .map(|b| b.size)
+        .sorted()
+        .rev()
+        .take(3)
+        .product();
     dbg!(&part2);
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn segments_example() {
+        let input: Heightmap = "2199943210\n3987894921\n9856789892\n8767896789\n9899965678"
+            .parse()
+            .unwrap();
+        let segmentation = segment(&input);
+        let sizes: Vec<usize> = segmentation
+            .basins
+            .iter()
+            .map(|b| b.size)
+            .sorted()
+            .collect();
+        assert_eq!(sizes, [3, 9, 9, 14]);
+        assert!(segmentation.basins.iter().all(|b| b.low_points.len() == 1));
+        assert_eq!(segmentation.labels[0], segmentation.labels[10]);
+        assert_eq!(segmentation.labels[2], None);
+    }
+
+    #[test]
+    fn reports_plateaus_and_multiple_minima() {
+        let input: Heightmap = "11912\n22923\n99999\n30103".parse().unwrap();
+        let segmentation = segment(&input);
+        let b = &segmentation.basins;
+        assert_eq!(b.len(), 3);
+        assert!(b[0].has_plateau() && b[0].low_points.is_empty());
+        assert_eq!(b[1].low_points, [3]);
+        assert_eq!(b[2].minima.len(), 2);
+        assert_eq!(b[2].low_points, [16, 18]);
+        assert_eq!(input.coordinates(18), [3, 3]);
+    }
+
+    #[test]
+    fn segments_volumes() {
+        // Both low points of the first layer drain into the same basin through the second
+        let input: Heightmap = "191\n\n222\n".parse().unwrap();
+        assert_eq!(input.dims, [3, 1, 2]);
+        let segmentation = segment(&input);
+        assert_eq!(segmentation.basins.len(), 1);
+        assert_eq!(segmentation.basins[0].size, 5);
+        assert_eq!(segmentation.basins[0].low_points, [0, 2]);
+        assert_eq!(input.coordinates(4), [1, 0, 1]);
+        assert_eq!(input.neighbors(4).sorted().collect_vec(), [1, 3, 5]);
+    }
+}
@@ -0,0 +1,138 @@
+use std::io::Write;
+
+use itertools::Itertools;
+
+use crate::{Heightmap, Segmentation};
+
+/// Side length in pixels of one heightmap cell in PPM images
+const CELL_PIXELS: usize = 4;
+
+const WALL_COLOR: [u8; 3] = [40, 40, 40];
+const LOW_POINT_COLOR: [u8; 3] = [255, 255, 255];
+
+fn hsv_to_rgb(h: f64, s: f64, v: f64) -> [u8; 3] {
+    let c = v * s;
+    let h = (h.rem_euclid(1.0)) * 6.0;
+    let x = c * (1.0 - (h % 2.0 - 1.0).abs());
+    let (r, g, b) = match h as u32 {
+        0 => (c, x, 0.0),
+        1 => (x, c, 0.0),
+        2 => (0.0, c, x),
+        3 => (0.0, x, c),
+        4 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+    let m = v - c;
+    [r, g, b].map(|channel| ((channel + m) * 255.0).round() as u8)
+}
+
+/// Colors of all basins. Hues are spread by the golden ratio so that neighboring labels differ;
+/// the three largest basins are drawn saturated, the others pale.
+pub struct Palette {
+    colors: Vec<[u8; 3]>,
+}
+
+impl Palette {
+    pub fn new(segmentation: &Segmentation) -> Palette {
+        let largest: Vec<usize> = segmentation
+            .basins
+            .iter()
+            .enumerate()
+            .sorted_by_key(|(_, b)| std::cmp::Reverse(b.size))
+            .take(3)
+            .map(|(i, _)| i)
+            .collect();
+        let colors = (0..segmentation.basins.len())
+            .map(|i| {
+                let hue = i as f64 * 0.618_033_988_75;
+                if largest.contains(&i) {
+                    hsv_to_rgb(hue, 0.9, 1.0)
+                } else {
+                    hsv_to_rgb(hue, 0.3, 0.6)
+                }
+            })
+            .collect();
+        Palette { colors }
+    }
+
+    fn color(&self, label: Option<usize>) -> [u8; 3] {
+        label.map_or(WALL_COLOR, |l| self.colors[l])
+    }
+}
+
+fn is_low_point(segmentation: &Segmentation, index: usize) -> bool {
+    segmentation.labels[index].is_some_and(|l| segmentation.basins[l].low_points.contains(&index))
+}
+
+/// Heights on 24-bit ANSI background colors, low points as `*`. Slices of higher dimensional
+/// maps are separated by blank lines.
+pub fn ansi(
+    map: &Heightmap,
+    segmentation: &Segmentation,
+    out: &mut impl Write,
+) -> std::io::Result<()> {
+    let palette = Palette::new(segmentation);
+    for (new_slice, row) in map.rows() {
+        if new_slice {
+            writeln!(out)?;
+        }
+        for index in row {
+            let [r, g, b] = palette.color(segmentation.labels[index]);
+            if is_low_point(segmentation, index) {
+                write!(
+                    out,
+                    "\x1b[48;2;{r};{g};{b}m\x1b[1;38;2;255;255;255m*\x1b[0m"
+                )?;
+            } else {
+                let height = map.heights[index];
+                write!(out, "\x1b[48;2;{r};{g};{b}m\x1b[38;2;0;0;0m{height}\x1b[0m")?;
+            }
+        }
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+/// Binary PPM with every cell as a square, low points as a white dot in its center. Slices of
+/// higher dimensional maps are stacked vertically, separated by a wall colored row.
+pub fn ppm(
+    map: &Heightmap,
+    segmentation: &Segmentation,
+    out: &mut impl Write,
+) -> std::io::Result<()> {
+    let palette = Palette::new(segmentation);
+    let rows = map.rows().collect::<Vec<_>>();
+    let separators = rows.iter().filter(|(new_slice, _)| *new_slice).count();
+    let width = map.width() * CELL_PIXELS;
+    write!(
+        out,
+        "P6\n{} {}\n255\n",
+        width,
+        (rows.len() + separators) * CELL_PIXELS
+    )?;
+
+    let dot = CELL_PIXELS / 4..CELL_PIXELS - CELL_PIXELS / 4;
+    for (new_slice, row) in rows {
+        if new_slice {
+            for _ in 0..CELL_PIXELS {
+                out.write_all(&WALL_COLOR.repeat(width))?;
+            }
+        }
+        for py in 0..CELL_PIXELS {
+            let mut line = Vec::with_capacity(width * 3);
+            for index in row.clone() {
+                let low_point = is_low_point(segmentation, index);
+                let color = palette.color(segmentation.labels[index]);
+                for px in 0..CELL_PIXELS {
+                    if low_point && dot.contains(&px) && dot.contains(&py) {
+                        line.extend(LOW_POINT_COLOR);
+                    } else {
+                        line.extend(color);
+                    }
+                }
+            }
+            out.write_all(&line)?;
+        }
+    }
+    Ok(())
+}
@@ -1,5 +1,6 @@
 use anyhow::Context;
 use itertools::Itertools;
+use num_bigint::BigInt;
 
 #[derive(thiserror::Error, Debug)]
 pub enum AocError {
@@ -9,81 +10,298 @@ pub enum AocError {
     ParseError(String),
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Pair {
+    open: char,
+    close: char,
+    /// Score of finding `close` where another closer was expected
+    corrupted_score: i64,
+    /// Score of `close` when completing a line
+    completion_score: i64,
+}
+
+/// Delimiter table and how lines are scored against it.
+///
+/// Definitions are one pair per line, `<open> <close> <corrupted score> <completion score>`,
+/// plus optionally `base <n>` for the completion score (`score * base + pair score`).
+/// Characters not in the table are ignored.
+#[derive(Debug, Clone)]
+struct Rules {
+    pairs: Vec<Pair>,
+    completion_base: i64,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        let pair = |open, close, corrupted_score, completion_score| Pair {
+            open,
+            close,
+            corrupted_score,
+            completion_score,
+        };
+        Rules {
+            pairs: vec![
+                pair('(', ')', 3, 1),
+                pair('[', ']', 57, 2),
+                pair('{', '}', 1197, 3),
+                pair('<', '>', 25137, 4),
+            ],
+            completion_base: 5,
+        }
+    }
+}
+
+impl std::str::FromStr for Rules {
+    type Err = AocError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut rules = Rules {
+            pairs: Vec::new(),
+            completion_base: 5,
+        };
+        let invalid = |line: &str| AocError::ParseError(format!("invalid rule \"{line}\""));
+        for line in s.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            let fields = line.split_whitespace().collect_vec();
+            match fields[..] {
+                ["base", base] => {
+                    rules.completion_base = base.parse().map_err(|_| invalid(line))?
+                }
+                [open, close, corrupted_score, completion_score] => {
+                    let single = |s: &str| s.chars().exactly_one().map_err(|_| invalid(line));
+                    rules.pairs.push(Pair {
+                        open: single(open)?,
+                        close: single(close)?,
+                        corrupted_score: corrupted_score.parse().map_err(|_| invalid(line))?,
+                        completion_score: completion_score.parse().map_err(|_| invalid(line))?,
+                    });
+                }
+                _ => return Err(invalid(line)),
+            }
+        }
+        let delimiters = rules.pairs.iter().flat_map(|p| [p.open, p.close]);
+        if rules.pairs.is_empty() || !delimiters.clone().all_unique() {
+            return Err(AocError::ParseError(
+                "rules need at least one pair and distinct delimiters".to_owned(),
+            ));
+        }
+        Ok(rules)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Status {
+    Ok,
+    Corrupted {
+        /// 1-based column of the first illegal character
+        column: usize,
+        found: char,
+        /// `None` if nothing was open
+        expected: Option<char>,
+    },
+    /// Pairs still open at the end, innermost last
+    Incomplete {
+        open: Vec<Pair>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Report {
+    status: Status,
+    /// Unbounded, as completion scores grow exponentially with the nesting depth
+    score: BigInt,
+}
+
+impl Rules {
+    fn lint(&self, line: &str) -> Report {
+        let mut stack: Vec<Pair> = Vec::new();
+        for (column, c) in line.chars().enumerate() {
+            if let Some(&pair) = self.pairs.iter().find(|p| p.open == c) {
+                stack.push(pair);
+            } else if let Some(pair) = self.pairs.iter().find(|p| p.close == c) {
+                let expected = stack.pop();
+                if expected.map(|e| e.close) != Some(c) {
+                    return Report {
+                        status: Status::Corrupted {
+                            column: column + 1,
+                            found: c,
+                            expected: expected.map(|e| e.close),
+                        },
+                        score: pair.corrupted_score.into(),
+                    };
+                }
+            }
+        }
+
+        if stack.is_empty() {
+            Report {
+                status: Status::Ok,
+                score: BigInt::default(),
+            }
+        } else {
+            let score = stack.iter().rev().fold(BigInt::default(), |acc, p| {
+                acc * self.completion_base + p.completion_score
+            });
+            Report {
+                status: Status::Incomplete { open: stack },
+                score,
+            }
+        }
+    }
+}
+
+impl Report {
+    /// Closers that complete an incomplete line
+    fn completion(&self) -> Option<String> {
+        match &self.status {
+            Status::Incomplete { open } => Some(open.iter().rev().map(|p| p.close).collect()),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Report {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.status {
+            Status::Ok => write!(f, "ok"),
+            Status::Corrupted {
+                column,
+                found,
+                expected: Some(expected),
+            } => write!(
+                f,
+                "corrupted at column {column}: expected '{expected}', found '{found}' (score {})",
+                self.score
+            ),
+            Status::Corrupted {
+                column,
+                found,
+                expected: None,
+            } => write!(
+                f,
+                "corrupted at column {column}: unexpected '{found}' (score {})",
+                self.score
+            ),
+            Status::Incomplete { .. } => write!(
+                f,
+                "incomplete, complete with \"{}\" (score {})",
+                self.completion().unwrap_or_default(),
+                self.score
+            ),
+        }
+    }
+}
+
 fn main() -> anyhow::Result<()> {
-    let file = std::env::args().nth(1).ok_or(AocError::NoInputFile)?;
+    let mut args = std::env::args().skip(1);
+    let file = args.next().ok_or(AocError::NoInputFile)?;
     let input = std::fs::read_to_string(file).context("Failed to read input file")?;
 
-    let part1: i64 = input
-        .lines()
-        .map(|l| {
-            let mut stack = Vec::new();
-            l.chars()
-                .map(|c| match c {
-                    '(' | '[' | '<' | '{' => {
-                        stack.push(c);
-                        0
-                    }
-                    ')' | ']' | '>' | '}' => {
-                        let expected = stack.pop();
-                        match (c, expected) {
-                            (')', Some(e)) if e != '(' => 3,
-                            (']', Some(e)) if e != '[' => 57,
-                            ('}', Some(e)) if e != '{' => 1197,
-                            ('>', Some(e)) if e != '<' => 25137,
-                            _ => 0,
-                        }
-                    }
-                    _ => 0,
-                })
-                .sum::<i64>()
-        })
-        .sum();
+    // Optional: --rules <pair table>, --report, --completions, --repair <out>
+    let mut rules = Rules::default();
+    let mut show_report = false;
+    let mut show_completions = false;
+    let mut repair_path = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--rules" => {
+                let path = args
+                    .next()
+                    .ok_or_else(|| AocError::ParseError("--rules needs a file".to_owned()))?;
+                rules = std::fs::read_to_string(&path)
+                    .with_context(|| format!("Failed to read rules {path}"))?
+                    .parse()?;
+            }
+            "--report" => show_report = true,
+            "--completions" => show_completions = true,
+            "--repair" => {
+                repair_path = Some(args.next().ok_or_else(|| {
+                    AocError::ParseError("--repair needs an output path".to_owned())
+                })?)
+            }
+            _ => return Err(AocError::ParseError(format!("unknown argument {arg}")).into()),
+        }
+    }
 
-    dbg!(&part1);
+    let reports = input.lines().map(|l| rules.lint(l)).collect_vec();
+    if show_report {
+        for (line, report) in reports.iter().enumerate() {
+            println!("line {}: {report}", line + 1);
+        }
+    }
 
-    let part2 = input
-        .lines()
-        .flat_map(|l| {
-            let mut stack = Vec::new();
-            let score = l
-                .chars()
-                .map(|c| match c {
-                    '(' | '[' | '<' | '{' => {
-                        stack.push(c);
-                        0
-                    }
-                    ')' | ']' | '>' | '}' => {
-                        let expected = stack.pop();
-                        match (c, expected) {
-                            (')', Some(e)) if e != '(' => 3,
-                            (']', Some(e)) if e != '[' => 57,
-                            ('}', Some(e)) if e != '{' => 1197,
-                            ('>', Some(e)) if e != '<' => 25137,
-                            _ => 0,
-                        }
-                    }
-                    _ => 0,
-                })
-                .sum::<i64>();
-            if score == 0 {
-                Some(stack.iter().rev().fold(0i64, |acc, c| {
-                    acc * 5
-                        + match c {
-                            '(' => 1,
-                            '[' => 2,
-                            '{' => 3,
-                            '<' => 4,
-                            _ => unreachable!(),
-                        }
-                }))
-            } else {
-                None
+    if show_completions {
+        for (line, report) in reports.iter().enumerate() {
+            if let Some(completion) = report.completion() {
+                println!("line {}: {completion}", line + 1);
             }
-        })
+        }
+    }
+
+    // Incomplete lines get their completion appended, corrupted ones cannot be fixed that way
+    if let Some(path) = repair_path {
+        let repaired = input
+            .lines()
+            .zip(reports.iter())
+            .map(|(l, r)| l.to_owned() + &r.completion().unwrap_or_default() + "\n")
+            .collect::<String>();
+        std::fs::write(&path, repaired).with_context(|| format!("Failed to write {path}"))?;
+        let corrupted = reports
+            .iter()
+            .filter(|r| matches!(r.status, Status::Corrupted { .. }))
+            .count();
+        if corrupted > 0 {
+            eprintln!("warning: {corrupted} corrupted lines were left unchanged");
+        }
+    }
+
+    let part1: BigInt = reports
+        .iter()
+        .filter(|r| matches!(r.status, Status::Corrupted { .. }))
+        .map(|r| &r.score)
+        .sum();
+    dbg!(&part1);
+
+    let part2 = reports
+        .iter()
+        .filter(|r| matches!(r.status, Status::Incomplete { .. }))
+        .map(|r| &r.score)
         .sorted()
         .collect_vec();
-    let part2 = part2[part2.len() / 2];
+    let part2 = part2.get(part2.len() / 2);
     dbg!(&part2);
 
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn lints_lines() {
+        let rules = Rules::default();
+        assert_eq!(
+            rules.lint("{([(<{}[<>[]}>{[]{[(<()>").status,
+            Status::Corrupted {
+                column: 13,
+                found: '}',
+                expected: Some(']')
+            }
+        );
+        let report = rules.lint("[({(<(())[]>[[{[]{<()<>>");
+        assert_eq!(report.score, 288957.into());
+        assert_eq!(report.completion().as_deref(), Some("}}]])})]"));
+        assert_eq!(rules.lint("(a)[b]").status, Status::Ok);
+
+        let rules: Rules = "base 10\n< > 1 2\n/ \\ 7 9".parse().unwrap();
+        assert_eq!(rules.lint("/<>").score, 9.into());
+        assert_eq!(rules.lint("</").score, (9 * 10 + 2).into());
+        assert_eq!(rules.lint("/<\\").score, 7.into());
+        assert!("( ) 3".parse::<Rules>().is_err());
+
+        // Deep nesting overflows any fixed-width completion score
+        let report = Rules::default().lint(&"(".repeat(30));
+        let ones = (BigInt::from(5).pow(30) - 1) / 4;
+        assert_eq!(report.score, ones);
+    }
+}
@@ -0,0 +1,109 @@
+use std::io::Write;
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::Context;
+
+use crate::Grid;
+
+/// Side length in pixels of one octopus in PPM frames
+const CELL_PIXELS: usize = 8;
+
+const FLASH_COLOR: [u8; 3] = [255, 240, 160];
+
+/// Dark blue for drained octopuses up to cyan just before flashing
+fn energy_color(energy: u32, threshold: u32) -> [u8; 3] {
+    let t = energy.min(threshold) as f64 / threshold.max(1) as f64;
+    let lerp = |a: f64, b: f64| (a + (b - a) * t).round() as u8;
+    [lerp(10.0, 40.0), lerp(20.0, 200.0), lerp(60.0, 230.0)]
+}
+
+fn cell_color(grid: &Grid, index: usize, flashed: &[bool]) -> [u8; 3] {
+    if flashed[index] {
+        FLASH_COLOR
+    } else {
+        energy_color(grid.energy[index], grid.threshold)
+    }
+}
+
+/// States from the initial grid up to and including `steps` steps, each with the octopuses that
+/// flashed to get there.
+fn frames(grid: &Grid, steps: usize) -> impl Iterator<Item = (Grid, Vec<bool>)> {
+    let initial = (grid.clone(), vec![false; grid.len()]);
+    std::iter::successors(Some(initial), |(grid, _)| {
+        let mut next = grid.clone();
+        let mut flashed = vec![false; next.len()];
+        for index in next.step() {
+            flashed[index] = true;
+        }
+        Some((next, flashed))
+    })
+    .take(steps + 1)
+}
+
+fn ansi_frame(
+    grid: &Grid,
+    flashed: &[bool],
+    step: usize,
+    out: &mut impl Write,
+) -> std::io::Result<()> {
+    // Cursor to the top left instead of clearing avoids flicker
+    write!(out, "\x1b[H")?;
+    writeln!(
+        out,
+        "step {step}: {} flashes\x1b[K",
+        flashed.iter().filter(|&&f| f).count()
+    )?;
+    for row in 0..grid.height {
+        for index in row * grid.width..(row + 1) * grid.width {
+            let [r, g, b] = cell_color(grid, index, flashed);
+            write!(out, "\x1b[48;2;{r};{g};{b}m  ")?;
+        }
+        writeln!(out, "\x1b[0m")?;
+    }
+    out.flush()
+}
+
+/// Plays `steps` steps in the terminal at `fps` frames per second.
+pub fn play(grid: &Grid, steps: usize, fps: f64) -> std::io::Result<()> {
+    let delay = Duration::from_secs_f64(1.0 / fps);
+    let mut out = std::io::stdout().lock();
+    write!(out, "\x1b[2J")?;
+    for (step, (grid, flashed)) in frames(grid, steps).enumerate() {
+        ansi_frame(&grid, &flashed, step, &mut out)?;
+        std::thread::sleep(delay);
+    }
+    Ok(())
+}
+
+fn write_ppm(grid: &Grid, flashed: &[bool], out: &mut impl Write) -> std::io::Result<()> {
+    write!(
+        out,
+        "P6\n{} {}\n255\n",
+        grid.width * CELL_PIXELS,
+        grid.height * CELL_PIXELS
+    )?;
+    for row in 0..grid.height {
+        let line: Vec<u8> = (row * grid.width..(row + 1) * grid.width)
+            .flat_map(|index| cell_color(grid, index, flashed).repeat(CELL_PIXELS))
+            .collect();
+        for _ in 0..CELL_PIXELS {
+            out.write_all(&line)?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes `frame_00000.ppm` for the initial grid and one more image per step into `dir`.
+pub fn export(grid: &Grid, steps: usize, dir: &Path) -> anyhow::Result<()> {
+    std::fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    for (step, (grid, flashed)) in frames(grid, steps).enumerate() {
+        let path = dir.join(format!("frame_{step:05}.ppm"));
+        let file = std::fs::File::create(&path)
+            .with_context(|| format!("Failed to create {}", path.display()))?;
+        let mut out = std::io::BufWriter::new(file);
+        write_ppm(&grid, &flashed, &mut out)?;
+        out.flush()?;
+    }
+    Ok(())
+}
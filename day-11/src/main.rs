@@ -1,5 +1,10 @@
+mod animation;
+
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::path::PathBuf;
+
 use anyhow::Context;
-use itertools::Itertools;
 
 #[derive(thiserror::Error, Debug)]
 pub enum AocError {
@@ -20,64 +25,287 @@ const OFFSETS: [(i64, i64); 8] = [
     (1, -1),
 ];
 
-fn get_mut(array: &mut Vec<Vec<i64>>, (x, y): (i64, i64)) -> Option<&mut i64> {
-    array
-        .get_mut((y) as usize)
-        .and_then(|v| v.get_mut((x) as usize))
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Grid {
+    width: usize,
+    height: usize,
+    /// Row-major energy levels
+    energy: Vec<u32>,
+    /// An octopus flashes once its energy exceeds this
+    threshold: u32,
 }
 
-fn process(array: &mut Vec<Vec<i64>>, pos: (i64, i64)) -> usize {
-    let mut sum = 0;
-    if let Some(value) = get_mut(array, pos) {
-        *value += 1;
-        if *value == 10 {
-            sum += 1;
-            sum += OFFSETS
-                .map(|(dx, dy)| (pos.0 + dx, pos.1 + dy))
-                .map(|pos| process(array, pos))
-                .iter()
-                .sum::<usize>();
+impl Grid {
+    fn parse(input: &str, threshold: u32) -> Result<Grid, AocError> {
+        let rows: Vec<Vec<u32>> = input
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty())
+            .map(|l| {
+                l.chars()
+                    .map(|c| c.to_digit(10))
+                    .collect::<Option<_>>()
+                    .ok_or_else(|| AocError::ParseError(format!("non-digit in \"{l}\"")))
+            })
+            .collect::<Result<_, _>>()?;
+        let width = rows.first().map_or(0, Vec::len);
+        if width == 0 || rows.iter().any(|r| r.len() != width) {
+            return Err(AocError::ParseError(
+                "grid must be non-empty and rectangular".to_owned(),
+            ));
         }
+        Ok(Grid {
+            width,
+            height: rows.len(),
+            energy: rows.into_iter().flatten().collect(),
+            threshold,
+        })
     }
-    sum
-}
 
-fn step(array: &mut Vec<Vec<i64>>) -> usize {
-    let mut sum = 0;
-    for y in 0..array.len() {
-        for x in 0..array[0].len() {
-            sum += process(array, (x.try_into().unwrap(), y.try_into().unwrap()));
-        }
+    fn len(&self) -> usize {
+        self.energy.len()
+    }
+
+    fn neighbors(&self, index: usize) -> impl Iterator<Item = usize> {
+        let (width, height) = (self.width as i64, self.height as i64);
+        let (x, y) = (index as i64 % width, index as i64 / width);
+        OFFSETS.iter().filter_map(move |&(dx, dy)| {
+            let (x, y) = (x + dx, y + dy);
+            let inside = (0..width).contains(&x) && (0..height).contains(&y);
+            inside.then_some((y * width + x) as usize)
+        })
     }
 
-    for value in array.iter_mut().flatten() {
-        if *value > 9 {
-            *value = 0;
+    /// Advances one step and returns the octopuses that flashed, in cascade order.
+    ///
+    /// Each octopus is pushed onto the worklist the first time its energy is above the
+    /// threshold, so every flash is handled once and the cascade needs no recursion. Energies
+    /// that start above the threshold flash in the first step like any other.
+    fn step(&mut self) -> Vec<usize> {
+        let mut queued = vec![false; self.len()];
+        let mut worklist = Vec::new();
+        for (index, energy) in self.energy.iter_mut().enumerate() {
+            *energy += 1;
+            if *energy > self.threshold {
+                queued[index] = true;
+                worklist.push(index);
+            }
         }
+
+        let mut flashed = Vec::new();
+        while let Some(index) = worklist.pop() {
+            flashed.push(index);
+            for n in self.neighbors(index) {
+                self.energy[n] += 1;
+                if self.energy[n] > self.threshold && !queued[n] {
+                    queued[n] = true;
+                    worklist.push(n);
+                }
+            }
+        }
+
+        for &index in flashed.iter() {
+            self.energy[index] = 0;
+        }
+        flashed
     }
+}
 
-    sum
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Analysis {
+    /// Flashes during each step, starting with step 1
+    flashes: Vec<usize>,
+    /// First step in which every octopus flashed
+    first_sync: Option<usize>,
+    /// `(start, length)`: the state after `start` steps recurs every `length` steps
+    cycle: Option<(usize, usize)>,
+}
+
+impl Analysis {
+    /// With a known cycle all future states have already been seen, so if none of them was
+    /// synchronized the grid never will be.
+    fn never_syncs(&self) -> bool {
+        self.first_sync.is_none() && self.cycle.is_some()
+    }
+}
+
+fn state_hash(grid: &Grid) -> u64 {
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    grid.energy.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Simulates until the state repeats or `max_steps` is reached. After the first step every
+/// energy is at most the threshold, so the grid has finitely many states and must repeat.
+fn analyze(grid: &Grid, max_steps: usize) -> Analysis {
+    let mut analysis = Analysis {
+        flashes: Vec::new(),
+        first_sync: None,
+        cycle: None,
+    };
+    let mut seen = HashMap::from([(state_hash(grid), 0)]);
+    let mut current = grid.clone();
+
+    for step in 1..=max_steps {
+        let flashes = current.step().len();
+        analysis.flashes.push(flashes);
+        if flashes == current.len() && analysis.first_sync.is_none() {
+            analysis.first_sync = Some(step);
+        }
+
+        let hash = state_hash(&current);
+        if let Some(&start) = seen.get(&hash) {
+            // Only hashes are stored, so rule out a collision by replaying
+            let mut earlier = grid.clone();
+            (0..start).for_each(|_| {
+                earlier.step();
+            });
+            if earlier.energy == current.energy {
+                analysis.cycle = Some((start, step - start));
+                break;
+            }
+        }
+        seen.insert(hash, step);
+    }
+    analysis
 }
 
 fn main() -> anyhow::Result<()> {
-    let file = std::env::args().nth(1).ok_or(AocError::NoInputFile)?;
+    let mut args = std::env::args().skip(1);
+    let file = args.next().ok_or(AocError::NoInputFile)?;
     let input = std::fs::read_to_string(file).context("Failed to read input file")?;
 
-    let mut input: Vec<Vec<i64>> = input
-        .lines()
-        .map(|l| l.chars().flat_map(|c| format!("{}", c).parse()).collect())
-        .collect();
+    // Optional: --threshold N (default 9), --max-steps N, --series,
+    // --animate and/or --frames <dir> with --steps N (default 100), --fps F (default 10)
+    let mut threshold = 9;
+    let mut max_steps = 1_000_000;
+    let mut series = false;
+    let mut animate = false;
+    let mut frames_dir = None;
+    let mut animation_steps = 100;
+    let mut fps = 10.0;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--threshold" => {
+                threshold = args
+                    .next()
+                    .and_then(|t| t.parse().ok())
+                    .ok_or_else(|| AocError::ParseError("--threshold needs a number".to_owned()))?
+            }
+            "--max-steps" => {
+                max_steps = args
+                    .next()
+                    .and_then(|t| t.parse().ok())
+                    .ok_or_else(|| AocError::ParseError("--max-steps needs a number".to_owned()))?
+            }
+            "--series" => series = true,
+            "--animate" => animate = true,
+            "--frames" => {
+                frames_dir = Some(PathBuf::from(args.next().ok_or_else(|| {
+                    AocError::ParseError("--frames needs a directory".to_owned())
+                })?))
+            }
+            "--steps" => {
+                animation_steps = args
+                    .next()
+                    .and_then(|t| t.parse().ok())
+                    .ok_or_else(|| AocError::ParseError("--steps needs a number".to_owned()))?
+            }
+            "--fps" => {
+                fps = args
+                    .next()
+                    .and_then(|t| t.parse().ok())
+                    .filter(|&fps: &f64| fps > 0.0)
+                    .ok_or_else(|| {
+                        AocError::ParseError("--fps needs a positive number".to_owned())
+                    })?
+            }
+            _ => return Err(AocError::ParseError(format!("unknown argument {arg}")).into()),
+        }
+    }
+
+    let input = Grid::parse(&input, threshold)?;
+
+    if animate {
+        animation::play(&input, animation_steps, fps)?;
+    }
+    if let Some(dir) = frames_dir {
+        animation::export(&input, animation_steps, &dir)?;
+    }
 
     let mut input_clone = input.clone();
-    let part1: usize = (0..100).map(|_| step(&mut input_clone)).sum();
+    let part1: usize = (0..100).map(|_| input_clone.step().len()).sum();
     dbg!(&part1);
 
-    let num_octopusses = input.len() * input[0].len();
-    let part2 = (0usize..)
-        .map(|_| step(&mut input))
-        .find_position(|&flashes| flashes == num_octopusses)
-        .and_then(|(index, _)| Some(index + 1))
-        .unwrap();
+    let analysis = analyze(&input, max_steps);
+    if series {
+        println!("step,flashes");
+        for (step, flashes) in analysis.flashes.iter().enumerate() {
+            println!("{},{flashes}", step + 1);
+        }
+    }
+    if let Some((start, length)) = analysis.cycle {
+        println!("state after step {start} recurs every {length} steps");
+    }
+
+    let part2 = analysis.first_sync;
     dbg!(&part2);
+    if analysis.never_syncs() {
+        println!("never synchronizes: the cycle contains no synchronized step");
+    } else if part2.is_none() {
+        println!("no synchronization or cycle within {max_steps} steps");
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn cascades_without_recursion() {
+        let mut grid = Grid::parse("11111\n19991\n19191\n19991\n11111", 9).unwrap();
+        assert_eq!(grid.step().len(), 9);
+        assert_eq!(
+            grid,
+            Grid::parse("34543\n40004\n50005\n40004\n34543", 9).unwrap()
+        );
+
+        // Large grids would overflow the stack of a recursive cascade
+        let row = "9".repeat(1000) + "\n";
+        let mut grid = Grid::parse(&row.repeat(1000), 9).unwrap();
+        assert_eq!(grid.step().len(), 1000 * 1000);
+        assert!(grid.energy.iter().all(|&e| e == 0));
+    }
+
+    #[test]
+    fn detects_cycles() {
+        let example = "5483143223\n2745854711\n5264556173\n6141336146\n6357385478\n\
+                       4167524645\n2176841721\n6882881134\n4846848554\n5283751526";
+        let analysis = analyze(&Grid::parse(example, 9).unwrap(), 1000);
+        assert_eq!(analysis.first_sync, Some(195));
+        // Synchronized grids are all zero and stay synchronized every 10 steps
+        assert_eq!(analysis.cycle, Some((195, 10)));
+        assert_eq!(analysis.flashes[..2], [0, 35]);
+
+        // Two neighbors pushing each other along never flash in the same step
+        let analysis = analyze(&Grid::parse("90", 9).unwrap(), 1000);
+        assert!(analysis.never_syncs());
+        assert_eq!(analysis.cycle, Some((0, 9)));
+    }
+
+    #[test]
+    fn flashes_above_threshold() {
+        // The 9 starts above the threshold and must still flash
+        let grid = Grid::parse("19\n11", 5).unwrap();
+        let mut stepped = grid.clone();
+        assert_eq!(stepped.step(), [1]);
+        assert_eq!(stepped, Grid::parse("30\n33", 5).unwrap());
+
+        // Three octopuses reach 6 in step 4 and push the fourth over the threshold
+        let analysis = analyze(&grid, 100);
+        assert_eq!(analysis.flashes[..4], [1, 0, 0, 4]);
+        assert_eq!(analysis.first_sync, Some(4));
+        assert_eq!(analysis.cycle, Some((4, 6)));
+    }
+}
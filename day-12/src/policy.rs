@@ -0,0 +1,134 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{AocError, CaveSystem};
+
+/// Which caves may be entered how often.
+///
+/// By default big caves are unlimited and small caves may be visited once, except that up to
+/// `revisits` distinct small caves may be visited a second time. Caps override this for single
+/// caves, big or small, and forbidden caves are never entered. `start` and `end` are always
+/// visited exactly once.
+#[derive(Debug, Clone, Default)]
+pub struct Policy {
+    pub revisits: usize,
+    pub caps: HashMap<String, u32>,
+    pub forbidden: HashSet<String>,
+}
+
+/// A policy resolved against a cave system.
+///
+/// The visit count of every limited cave has its own bit field in a `u128`, so the whole visit
+/// state is a single integer that is cheap to copy and to use as memo key.
+#[derive(Debug, Clone)]
+pub struct Limits {
+    /// Maximum regular visits, `None` for unlimited caves
+    caps: Vec<Option<u32>>,
+    /// Small caves without an explicit cap may use up a revisit
+    revisitable: Vec<bool>,
+    /// `(offset, width)` of the bit field holding each cave's visit count
+    fields: Vec<(u32, u32)>,
+    revisits: usize,
+}
+
+/// Visit counts packed as described in [`Limits`] plus the revisits still available.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Visits {
+    counts: u128,
+    revisits_left: usize,
+}
+
+impl Policy {
+    pub fn resolve(&self, system: &CaveSystem) -> Result<Limits, AocError> {
+        for name in self.caps.keys().chain(&self.forbidden) {
+            match system.index(name) {
+                None => return Err(AocError::ParseError(format!("unknown cave {name}"))),
+                Some(cave) if cave == system.start || cave == system.end => {
+                    return Err(AocError::ParseError(format!(
+                        "{name} is always visited exactly once, it cannot be capped or forbidden"
+                    )))
+                }
+                Some(_) => (),
+            }
+        }
+
+        let mut limits = Limits {
+            caps: Vec::new(),
+            revisitable: Vec::new(),
+            fields: Vec::new(),
+            revisits: self.revisits,
+        };
+        let mut bits = 0;
+        for (index, name) in system.names.iter().enumerate() {
+            // Paths start in `start` and stop at `end`, so neither can be entered again
+            let explicit = if index == system.start || index == system.end {
+                Some(1)
+            } else if self.forbidden.contains(name) {
+                Some(0)
+            } else {
+                self.caps.get(name).copied()
+            };
+            let cap = explicit.or((system.small[index]).then_some(1));
+            let revisitable = system.small[index] && explicit.is_none();
+            let max_count = cap.unwrap_or(0) + u32::from(revisitable);
+            let width = u32::BITS - max_count.leading_zeros();
+
+            limits.caps.push(cap);
+            limits.revisitable.push(revisitable);
+            limits.fields.push((bits, width));
+            bits += width;
+        }
+        // Walking back and forth between two unlimited caves never ends
+        for (cave, neighbors) in system.neighbors.iter().enumerate() {
+            if let Some(&n) = neighbors
+                .iter()
+                .find(|&&n| limits.caps[cave].is_none() && limits.caps[n].is_none())
+            {
+                return Err(AocError::InfinitePaths(
+                    system.names[cave].clone(),
+                    system.names[n].clone(),
+                ));
+            }
+        }
+        if bits > u128::BITS {
+            return Err(AocError::ParseError(format!(
+                "visit state needs {bits} bits, only {} are supported",
+                u128::BITS
+            )));
+        }
+        Ok(limits)
+    }
+}
+
+impl Limits {
+    /// State after entering `start`
+    pub fn initial(&self, system: &CaveSystem) -> Visits {
+        Visits {
+            counts: 1 << self.fields[system.start].0,
+            revisits_left: self.revisits,
+        }
+    }
+
+    fn count(&self, visits: Visits, cave: usize) -> u32 {
+        let (offset, width) = self.fields[cave];
+        ((visits.counts >> offset) & ((1 << width) - 1)) as u32
+    }
+
+    /// State after entering `cave`, `None` if the policy forbids it
+    pub fn enter(&self, visits: Visits, cave: usize) -> Option<Visits> {
+        let Some(cap) = self.caps[cave] else {
+            return Some(visits);
+        };
+        let count = self.count(visits, cave);
+        let counts = visits.counts + (1 << self.fields[cave].0);
+        if count < cap {
+            Some(Visits { counts, ..visits })
+        } else if count == 1 && self.revisitable[cave] && visits.revisits_left > 0 {
+            Some(Visits {
+                counts,
+                revisits_left: visits.revisits_left - 1,
+            })
+        } else {
+            None
+        }
+    }
+}
@@ -1,6 +1,11 @@
+mod ocr;
+mod svg;
+
 use std::collections::HashSet;
+use std::io::Write;
 
 use anyhow::Context;
+use itertools::Itertools;
 use regex::Regex;
 
 #[derive(thiserror::Error, Debug)]
@@ -9,51 +14,204 @@ pub enum AocError {
     NoInputFile,
     #[error("Failed to parse: {0}")]
     ParseError(String),
+    #[error("Unknown glyph {0}:\n{1}")]
+    UnknownGlyph(usize, String),
+    #[error("Sheet does not look like text, {0}:\n{1}")]
+    NotText(String, String),
 }
 
-fn main() -> anyhow::Result<()> {
-    let file = std::env::args().nth(1).ok_or(AocError::NoInputFile)?;
-    let input = std::fs::read_to_string(file).context("Failed to read input file")?;
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Axis {
+    X,
+    Y,
+}
 
-    let re_dots = Regex::new(r"(\d+),(\d+)").unwrap();
-    let re_folds = Regex::new(r"fold along ([xy])=(\d+)").unwrap();
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Fold {
+    axis: Axis,
+    line: i64,
+}
 
-    let mut dots: HashSet<(i64, i64)> = re_dots
-        .captures_iter(&input)
-        .flat_map(|cap| Some((cap[1].parse().ok()?, cap[2].parse().ok()?)))
-        .collect();
+impl std::fmt::Display for Fold {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let axis = match self.axis {
+            Axis::X => 'x',
+            Axis::Y => 'y',
+        };
+        write!(f, "fold along {axis}={}", self.line)
+    }
+}
 
-    let folds: Vec<(char, i64)> = re_folds
-        .captures_iter(&input)
-        .flat_map(|cap| Some((cap[1].chars().next()?, cap[2].parse().ok()?)))
-        .collect();
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Sheet {
+    dots: HashSet<(i64, i64)>,
+}
 
-    for (axis, coordinate) in folds {
-        dots = dots
+impl Sheet {
+    /// `(min, max)` corners of the smallest rectangle containing all dots
+    fn bounds(&self) -> Option<((i64, i64), (i64, i64))> {
+        let (min_x, max_x) = self.dots.iter().map(|&(x, _)| x).minmax().into_option()?;
+        let (min_y, max_y) = self.dots.iter().map(|&(_, y)| y).minmax().into_option()?;
+        Some(((min_x, min_y), (max_x, max_y)))
+    }
+
+    /// Folds the part beyond the line onto the part before it. Dots on the line stay where they
+    /// are; their number is returned along with the folded sheet.
+    fn fold(&self, fold: Fold) -> (Sheet, usize) {
+        let mirror = |c: i64| if c < fold.line { c } else { 2 * fold.line - c };
+        let mut on_line = 0;
+        let dots = self
+            .dots
             .iter()
-            .map(|&(x, y)| match axis {
-                'x' => (
-                    if x < coordinate {
-                        x
-                    } else {
-                        2 * coordinate - x
-                    },
-                    y,
-                ),
-                'y' => (
-                    x,
-                    if y < coordinate {
-                        y
-                    } else {
-                        2 * coordinate - y
-                    },
-                ),
-                _ => unreachable!(),
+            .map(|&(x, y)| {
+                let c = match fold.axis {
+                    Axis::X => x,
+                    Axis::Y => y,
+                };
+                if c == fold.line {
+                    on_line += 1;
+                }
+                match fold.axis {
+                    Axis::X => (mirror(x), y),
+                    Axis::Y => (x, mirror(y)),
+                }
             })
             .collect();
-        dbg!(&dots.len());
+        (Sheet { dots }, on_line)
     }
+}
+
+/// The sheet after one fold
+#[derive(Debug, Clone)]
+struct FoldRecord {
+    fold: Fold,
+    sheet: Sheet,
+    bounds: Option<((i64, i64), (i64, i64))>,
+    /// Dots that lay on the fold line, which the puzzle promises never happens
+    on_line: usize,
+}
+
+impl std::fmt::Display for FoldRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {} dots", self.fold, self.sheet.dots.len())?;
+        if let Some(((min_x, min_y), (max_x, max_y))) = self.bounds {
+            write!(f, " within ({min_x},{min_y})-({max_x},{max_y})")?;
+        }
+        Ok(())
+    }
+}
+
+fn fold_all(sheet: Sheet, folds: &[Fold]) -> Vec<FoldRecord> {
+    folds
+        .iter()
+        .scan(sheet, |sheet, &fold| {
+            let (folded, on_line) = sheet.fold(fold);
+            *sheet = folded.clone();
+            Some(FoldRecord {
+                fold,
+                bounds: folded.bounds(),
+                sheet: folded,
+                on_line,
+            })
+        })
+        .collect()
+}
+
+fn parse(input: &str) -> Result<(Sheet, Vec<Fold>), AocError> {
+    let re_dot = Regex::new(r"^(\d+),(\d+)$").unwrap();
+    let re_fold = Regex::new(r"^fold along (\w+)=(\d+)$").unwrap();
+
+    let mut dots = HashSet::new();
+    let mut folds = Vec::new();
+    for line in input.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        let invalid = || AocError::ParseError(format!("invalid line \"{line}\""));
+        if let Some(cap) = re_dot.captures(line) {
+            dots.insert((
+                cap[1].parse().map_err(|_| invalid())?,
+                cap[2].parse().map_err(|_| invalid())?,
+            ));
+        } else if let Some(cap) = re_fold.captures(line) {
+            let axis = match &cap[1] {
+                "x" => Axis::X,
+                "y" => Axis::Y,
+                axis => {
+                    return Err(AocError::ParseError(format!(
+                        "invalid fold axis \"{axis}\""
+                    )))
+                }
+            };
+            folds.push(Fold {
+                axis,
+                line: cap[2].parse().map_err(|_| invalid())?,
+            });
+        } else {
+            return Err(invalid());
+        }
+    }
+    Ok((Sheet { dots }, folds))
+}
+
+fn main() -> anyhow::Result<()> {
+    let mut args = std::env::args().skip(1);
+    let file = args.next().ok_or(AocError::NoInputFile)?;
+    let input = std::fs::read_to_string(file).context("Failed to read input file")?;
 
+    // Optional: --font <file> (repeatable) adds or replaces glyphs for reading the code,
+    // --svg <dir> writes the sheet before and after every fold
+    let mut font = ocr::Font::puzzle();
+    let mut svg_dir = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--font" => {
+                let path = args
+                    .next()
+                    .ok_or_else(|| AocError::ParseError("--font needs a file".to_owned()))?;
+                font.extend(
+                    std::fs::read_to_string(&path)
+                        .with_context(|| format!("Failed to read font {path}"))?
+                        .parse()?,
+                )?;
+            }
+            "--svg" => {
+                svg_dir =
+                    Some(args.next().ok_or_else(|| {
+                        AocError::ParseError("--svg needs a directory".to_owned())
+                    })?)
+            }
+            _ => return Err(AocError::ParseError(format!("unknown argument {arg}")).into()),
+        }
+    }
+
+    let (sheet, folds) = parse(&input)?;
+    let records = fold_all(sheet.clone(), &folds);
+    for record in records.iter() {
+        println!("{record}");
+        if record.on_line > 0 {
+            eprintln!(
+                "warning: {} dots lie on the line of {}",
+                record.on_line, record.fold
+            );
+        }
+    }
+
+    if let Some(dir) = svg_dir {
+        std::fs::create_dir_all(&dir).with_context(|| format!("Failed to create {dir}"))?;
+        let sheets = std::iter::once(&sheet).chain(records.iter().map(|r| &r.sheet));
+        for (i, sheet) in sheets.enumerate() {
+            let path = std::path::Path::new(&dir).join(format!("sheet_{i:02}.svg"));
+            let mut out = std::io::BufWriter::new(
+                std::fs::File::create(&path)
+                    .with_context(|| format!("Failed to create {}", path.display()))?,
+            );
+            svg::write(sheet, folds.get(i), &mut out)?;
+            out.flush()?;
+        }
+    }
+
+    let part1 = records.first().map(|r| r.sheet.dots.len());
+    dbg!(&part1);
+
+    let dots = records.last().map_or(&sheet, |r| &r.sheet).dots.clone();
     let max_x = dots
         .iter()
         .map(|(x, _)| x)
@@ -68,8 +226,97 @@ fn main() -> anyhow::Result<()> {
                 print!(" ");
             }
         }
-        print!("\n");
+        println!();
     }
 
+    // Unknown glyphs are an error, but a sheet that is not text at all has no code to read
+    let part2 = match font.read(&dots) {
+        Ok(code) => Some(code),
+        Err(e @ AocError::NotText(..)) => {
+            eprintln!("warning: {e}");
+            None
+        }
+        Err(e) => return Err(e.into()),
+    };
+    dbg!(&part2);
+
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sheet(rows: &[&str]) -> HashSet<(i64, i64)> {
+        rows.iter()
+            .enumerate()
+            .flat_map(|(y, row)| {
+                row.chars()
+                    .enumerate()
+                    .filter(|&(_, c)| c == '#')
+                    .map(move |(x, _)| (x as i64, y as i64))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reads_glyphs() {
+        let font = ocr::Font::puzzle();
+        let dots = sheet(&[
+            ".##....##",
+            "#..#....#",
+            "#.......#",
+            "#.......#",
+            "#..#.#..#",
+            ".##...##.",
+        ]);
+        assert_eq!(font.read(&dots).unwrap(), "CJ");
+
+        let mut dots = dots;
+        dots.insert((3, 2));
+        let error = font.read(&dots).unwrap_err().to_string();
+        assert!(error.contains("found  C     diff"), "{error}");
+        assert!(error.contains("#..#   #...     +"), "{error}");
+
+        let mut font = font;
+        font.extend("X\n.##.\n#..#\n#..#\n#...\n#..#\n.##.".parse().unwrap())
+            .unwrap();
+        assert_eq!(font.read(&dots).unwrap(), "XJ");
+
+        // The example folds into a square, which is no text
+        let error = font
+            .read(&sheet(&["#####", "#...#"]))
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.contains("wider than 4 columns:\n#####\n#...#"),
+            "{error}"
+        );
+
+        let mut font: ocr::Font = "W\n#.....\n......".parse().unwrap();
+        font.extend("N\n.....#\n......".parse().unwrap()).unwrap();
+        let error = font.read(&sheet(&["#....#"])).unwrap_err().to_string();
+        assert!(
+            error.contains("found   W       diff\n#....#  #.....       +"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn records_folds() {
+        let (sheet, folds) = parse(
+            "6,10\n0,14\n9,10\n0,3\n10,4\n4,11\n6,0\n6,12\n4,1\n0,13\n\
+             10,12\n3,4\n3,0\n8,4\n1,10\n2,14\n8,10\n9,0\n\nfold along y=7\nfold along x=5",
+        )
+        .unwrap();
+        let records = fold_all(sheet, &folds);
+        assert_eq!(records[0].sheet.dots.len(), 17);
+        assert_eq!(records[1].sheet.dots.len(), 16);
+        assert_eq!(records[1].bounds, Some(((0, 0), (4, 4))));
+        assert!(records.iter().all(|r| r.on_line == 0));
+
+        let (sheet, folds) = parse("1,2\n3,2\nfold along x=3").unwrap();
+        assert_eq!(fold_all(sheet, &folds)[0].on_line, 1);
+        assert!(parse("1,2\nfold along z=3").is_err());
+    }
+}
@@ -0,0 +1,189 @@
+use std::collections::HashSet;
+
+use itertools::Itertools;
+
+use crate::AocError;
+
+/// Bitmap font of equally sized glyphs, read as text with one column of space between them.
+///
+/// Definitions are blocks separated by blank lines: the character, then one line per row with
+/// `#` for a dot and `.` for none. Lines starting with `//` are comments.
+#[derive(Debug, Clone)]
+pub struct Font {
+    width: usize,
+    height: usize,
+    glyphs: Vec<(char, Vec<bool>)>,
+}
+
+impl std::str::FromStr for Font {
+    type Err = AocError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lines = s
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.starts_with("//"))
+            .collect_vec();
+        let mut glyphs = Vec::new();
+        let mut size = None;
+        for block in lines.split(|l| l.is_empty()).filter(|b| !b.is_empty()) {
+            let invalid = || AocError::ParseError(format!("invalid glyph \"{}\"", block[0]));
+            let c = block[0].chars().exactly_one().map_err(|_| invalid())?;
+            let rows = &block[1..];
+            let width = rows.first().map_or(0, |r| r.len());
+            if width == 0 || rows.iter().any(|r| r.len() != width) {
+                return Err(invalid());
+            }
+            if *size.get_or_insert((width, rows.len())) != (width, rows.len()) {
+                return Err(AocError::ParseError(format!(
+                    "glyph {c} differs in size from the others"
+                )));
+            }
+            let bitmap = rows
+                .iter()
+                .flat_map(|r| r.chars())
+                .map(|p| match p {
+                    '#' => Ok(true),
+                    '.' => Ok(false),
+                    _ => Err(invalid()),
+                })
+                .collect::<Result<_, _>>()?;
+            glyphs.push((c, bitmap));
+        }
+        let (width, height) = size.ok_or_else(|| AocError::ParseError("empty font".to_owned()))?;
+        Ok(Font {
+            width,
+            height,
+            glyphs,
+        })
+    }
+}
+
+/// The whole sheet from the origin, or from further up and left if dots lie there
+fn render_sheet(dots: &HashSet<(i64, i64)>) -> String {
+    let (min_x, max_x) = dots
+        .iter()
+        .map(|&(x, _)| x)
+        .chain([0])
+        .minmax()
+        .into_option()
+        .unwrap();
+    let (min_y, max_y) = dots
+        .iter()
+        .map(|&(_, y)| y)
+        .chain([0])
+        .minmax()
+        .into_option()
+        .unwrap();
+    (min_y..=max_y)
+        .map(|y| {
+            (min_x..=max_x)
+                .map(|x| if dots.contains(&(x, y)) { '#' } else { '.' })
+                .collect::<String>()
+        })
+        .join("\n")
+}
+
+fn render(bitmap: &[bool], width: usize) -> Vec<String> {
+    bitmap
+        .chunks(width)
+        .map(|row| row.iter().map(|&d| if d { '#' } else { '.' }).collect())
+        .collect()
+}
+
+impl Font {
+    /// The font of the puzzle
+    pub fn puzzle() -> Font {
+        include_str!("../fonts/4x6").parse().unwrap()
+    }
+
+    /// Adds the glyphs of `other`, replacing those of the same characters
+    pub fn extend(&mut self, other: Font) -> Result<(), AocError> {
+        if (other.width, other.height) != (self.width, self.height) {
+            return Err(AocError::ParseError(format!(
+                "font is {}x{}, expected {}x{}",
+                other.width, other.height, self.width, self.height
+            )));
+        }
+        for (c, bitmap) in other.glyphs {
+            self.glyphs.retain(|&(g, _)| g != c);
+            self.glyphs.push((c, bitmap));
+        }
+        Ok(())
+    }
+
+    /// Side by side rendering of an unknown glyph, the most similar known one and where they
+    /// differ (`+` extra dot, `-` missing dot)
+    fn diff(&self, bitmap: &[bool]) -> String {
+        let closest = self
+            .glyphs
+            .iter()
+            .min_by_key(|(_, g)| g.iter().zip(bitmap).filter(|(a, b)| a != b).count());
+        let Some((c, glyph)) = closest else {
+            return render(bitmap, self.width).join("\n");
+        };
+        let marks = bitmap
+            .iter()
+            .zip(glyph)
+            .map(|(&found, &known)| match (found, known) {
+                (true, false) => '+',
+                (false, true) => '-',
+                _ => ' ',
+            })
+            .chunks(self.width)
+            .into_iter()
+            .map(|row| row.collect::<String>())
+            .collect_vec();
+        let w = self.width;
+        // The found column is at least as wide as its header
+        let found_w = w.max("found".len());
+        let header = format!("{:found_w$}  {c:w$}  diff", "found");
+        let rows = render(bitmap, w)
+            .into_iter()
+            .zip(render(glyph, w))
+            .zip(marks)
+            .map(|((found, known), marks)| format!("{found:found_w$}  {known:w$}  {marks}"));
+        std::iter::once(header).chain(rows).join("\n")
+    }
+
+    /// Reads the text spelled by `dots`, starting at the top left corner of the sheet.
+    pub fn read(&self, dots: &HashSet<(i64, i64)>) -> Result<String, AocError> {
+        if dots
+            .iter()
+            .any(|&(x, y)| x < 0 || y < 0 || y >= self.height as i64)
+        {
+            return Err(AocError::NotText(
+                format!("text must lie within the first {} rows", self.height),
+                render_sheet(dots),
+            ));
+        }
+        let max_x = dots
+            .iter()
+            .map(|&(x, _)| x as usize)
+            .max()
+            .ok_or_else(|| AocError::ParseError("no dots to read".to_owned()))?;
+
+        let pitch = self.width + 1;
+        let count = max_x / pitch + 1;
+        (0..count)
+            .map(|i| {
+                let left = (i * pitch) as i64;
+                if (0..self.height).any(|y| dots.contains(&(left + self.width as i64, y as i64))) {
+                    return Err(AocError::NotText(
+                        format!("glyph {i} is wider than {} columns", self.width),
+                        render_sheet(dots),
+                    ));
+                }
+                let bitmap = (0..self.height)
+                    .cartesian_product(0..self.width)
+                    .map(|(y, x)| dots.contains(&(left + x as i64, y as i64)))
+                    .collect_vec();
+                self.glyphs
+                    .iter()
+                    .find(|(_, g)| *g == bitmap)
+                    .map(|&(c, _)| c)
+                    .ok_or_else(|| AocError::UnknownGlyph(i, self.diff(&bitmap)))
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,35 @@
+use std::io::Write;
+
+use crate::{Axis, Fold, Sheet};
+
+/// Every dot as a unit square on white, with the next fold line dashed in red.
+pub fn write(sheet: &Sheet, next_fold: Option<&Fold>, out: &mut impl Write) -> std::io::Result<()> {
+    let ((_, _), (max_x, max_y)) = sheet.bounds().unwrap_or(((0, 0), (0, 0)));
+    let (width, height) = (max_x.max(0) + 1, max_y.max(0) + 1);
+    writeln!(
+        out,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 {width} {height}" width="{}" height="{}">"#,
+        width * 4,
+        height * 4
+    )?;
+    writeln!(out, r#"<rect width="100%" height="100%" fill="white"/>"#)?;
+    for &(x, y) in sheet.dots.iter() {
+        writeln!(
+            out,
+            r#"<rect x="{x}" y="{y}" width="1" height="1" fill="black"/>"#
+        )?;
+    }
+    if let Some(fold) = next_fold {
+        // Through the middle of the cells on the line
+        let c = fold.line as f64 + 0.5;
+        let (x1, y1, x2, y2) = match fold.axis {
+            Axis::X => (c, 0.0, c, height as f64),
+            Axis::Y => (0.0, c, width as f64, c),
+        };
+        writeln!(
+            out,
+            r#"<line x1="{x1}" y1="{y1}" x2="{x2}" y2="{y2}" stroke="red" stroke-width="0.3" stroke-dasharray="1"/>"#
+        )?;
+    }
+    writeln!(out, "</svg>")
+}
@@ -0,0 +1,188 @@
+use itertools::Itertools;
+
+use crate::AocError;
+
+/// Risk levels of a rectangular map, stored flat in row-major order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Grid {
+    pub width: usize,
+    pub height: usize,
+    pub risk: Vec<u32>,
+}
+
+impl std::str::FromStr for Grid {
+    type Err = AocError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let rows: Vec<Vec<u32>> = s
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty())
+            .map(|l| {
+                l.chars()
+                    .map(|c| c.to_digit(10))
+                    .collect::<Option<_>>()
+                    .ok_or_else(|| AocError::ParseError(format!("non-digit in \"{l}\"")))
+            })
+            .collect::<Result<_, _>>()?;
+        let width = rows.first().map_or(0, Vec::len);
+        if width == 0 || rows.iter().any(|r| r.len() != width) {
+            return Err(AocError::ParseError(
+                "grid must be non-empty and rectangular".to_owned(),
+            ));
+        }
+        Ok(Grid {
+            width,
+            height: rows.len(),
+            risk: rows.into_iter().flatten().collect(),
+        })
+    }
+}
+
+/// Anything that assigns risks to the cells of a rectangle, indexed row-major
+pub trait RiskMap {
+    fn width(&self) -> usize;
+    fn height(&self) -> usize;
+    fn risk(&self, index: usize) -> u32;
+
+    fn len(&self) -> usize {
+        self.width() * self.height()
+    }
+
+    fn coordinates(&self, index: usize) -> (usize, usize) {
+        (index % self.width(), index / self.width())
+    }
+
+    fn neighbors(&self, index: usize) -> impl Iterator<Item = usize> {
+        let (width, len) = (self.width(), self.len());
+        let (x, y) = self.coordinates(index);
+        let left = (x > 0).then(|| index - 1);
+        let right = (x + 1 < width).then_some(index + 1);
+        let up = (y > 0).then(|| index - width);
+        let down = (index + width < len).then_some(index + width);
+        [right, down, left, up].into_iter().flatten()
+    }
+
+    /// Manhattan distance between two cells
+    fn distance(&self, a: usize, b: usize) -> usize {
+        let ((ax, ay), (bx, by)) = (self.coordinates(a), self.coordinates(b));
+        ax.abs_diff(bx) + ay.abs_diff(by)
+    }
+
+    /// Smallest and largest risk of any cell
+    fn risk_range(&self) -> (u32, u32) {
+        (0..self.len())
+            .map(|i| self.risk(i))
+            .minmax()
+            .into_option()
+            .unwrap_or((0, 0))
+    }
+}
+
+impl RiskMap for Grid {
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn risk(&self, index: usize) -> u32 {
+        self.risk[index]
+    }
+}
+
+/// How risks change from one tile to the next: `increment` is added per tile to the right or
+/// down, and results beyond `max` wrap around to `min`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Increment {
+    pub increment: u32,
+    pub min: u32,
+    pub max: u32,
+}
+
+impl Default for Increment {
+    fn default() -> Self {
+        Increment {
+            increment: 1,
+            min: 1,
+            max: 9,
+        }
+    }
+}
+
+impl Increment {
+    fn apply(&self, risk: u32, tile: usize) -> u32 {
+        let range = u64::from(self.max - self.min + 1);
+        let shifted = u64::from(risk - self.min) + tile as u64 * u64::from(self.increment);
+        (shifted % range) as u32 + self.min
+    }
+}
+
+/// A grid repeated `factor.0` times along x and `factor.1` times along y, computing the risk of
+/// every cell when asked instead of storing the whole map.
+#[derive(Debug, Clone, Copy)]
+pub struct Tiled<'a> {
+    grid: &'a Grid,
+    factor: (usize, usize),
+    increment: Increment,
+}
+
+impl<'a> Tiled<'a> {
+    /// Fails if a risk of `grid` lies outside the range `increment` wraps around in
+    pub fn new(
+        grid: &'a Grid,
+        factor: (usize, usize),
+        increment: Increment,
+    ) -> Result<Self, AocError> {
+        if let Some(risk) = grid
+            .risk
+            .iter()
+            .find(|&&r| r < increment.min || r > increment.max)
+        {
+            return Err(AocError::ParseError(format!(
+                "risk {risk} lies outside the wrap range {}-{}",
+                increment.min, increment.max
+            )));
+        }
+        Ok(Tiled {
+            grid,
+            factor,
+            increment,
+        })
+    }
+}
+
+impl RiskMap for Tiled<'_> {
+    fn width(&self) -> usize {
+        self.grid.width * self.factor.0
+    }
+
+    fn height(&self) -> usize {
+        self.grid.height * self.factor.1
+    }
+
+    fn risk(&self, index: usize) -> u32 {
+        let (x, y) = self.coordinates(index);
+        let (tile_x, x) = (x / self.grid.width, x % self.grid.width);
+        let (tile_y, y) = (y / self.grid.height, y % self.grid.height);
+        let risk = self.grid.risk[y * self.grid.width + x];
+        self.increment.apply(risk, tile_x + tile_y)
+    }
+
+    /// Tiles only differ by their shift, so one copy of the grid per distinct shift is enough
+    fn risk_range(&self) -> (u32, u32) {
+        let tiles = (self.factor.0 + self.factor.1).saturating_sub(1);
+        (0..tiles.min((self.increment.max - self.increment.min + 1) as usize))
+            .flat_map(|tile| {
+                self.grid
+                    .risk
+                    .iter()
+                    .map(move |&r| self.increment.apply(r, tile))
+            })
+            .minmax()
+            .into_option()
+            .unwrap_or((0, 0))
+    }
+}
@@ -1,11 +1,16 @@
-#![feature(array_zip)]
+mod grid;
+mod search;
 
 use std::cmp::min;
 use std::collections::HashSet;
+use std::time::Instant;
 
 use anyhow::Context;
 use itertools::Itertools;
 
+use grid::{Grid, Increment, RiskMap, Tiled};
+use search::Route;
+
 #[derive(thiserror::Error, Debug)]
 pub enum AocError {
     #[error("No input file provided")]
@@ -17,7 +22,7 @@ pub enum AocError {
 const OFFSETS: [(i64, i64); 4] = [(1, 0), (0, 1), (-1, 0), (0, -1)];
 
 fn offset(
-    array: &Vec<Vec<i64>>,
+    array: &[Vec<i64>],
     (x, y): (i64, i64),
     (dx, dy): (i64, i64),
     extend: bool,
@@ -29,33 +34,34 @@ fn offset(
         array
             .get((y + dy) as usize % array.len())
             .and_then(|v| v.get((x + dx) as usize % array[0].len()))
-            .and_then(|&value| Some((((value + penalty) - 1) % 9) + 1))
+            .map(|&value| (((value + penalty) - 1) % 9) + 1)
     } else {
         array
             .get((y + dy) as usize)
             .and_then(|v| v.get((x + dx) as usize))
-            .and_then(|&v| Some(v))
+            .copied()
     }
 }
-fn offset_mut<'array>(
-    array: &'array mut Vec<Vec<i64>>,
+fn offset_mut(
+    array: &mut [Vec<i64>],
     (x, y): (i64, i64),
     (dx, dy): (i64, i64),
-) -> Option<&'array mut i64> {
+) -> Option<&mut i64> {
     array
         .get_mut((y + dy) as usize)
         .and_then(|v| v.get_mut((x + dx) as usize))
 }
 
-fn get(array: &Vec<Vec<i64>>, pos: (i64, i64)) -> Option<i64> {
+fn get(array: &[Vec<i64>], pos: (i64, i64)) -> Option<i64> {
     offset(array, pos, (0, 0), false)
 }
 
-fn neighbors(array: &Vec<Vec<i64>>, pos: (i64, i64), extend: bool) -> [Option<i64>; 4] {
+fn neighbors(array: &[Vec<i64>], pos: (i64, i64), extend: bool) -> [Option<i64>; 4] {
     OFFSETS.map(|o| offset(array, pos, o, extend))
 }
 
-fn dijkstra(input: &Vec<Vec<i64>>, part2: bool) -> anyhow::Result<i64> {
+/// The original search on nested vectors, kept as reference for `--compare`
+fn dijkstra(input: &[Vec<i64>], part2: bool) -> anyhow::Result<i64> {
     let start = (0, 0);
     let mut goal = (
         (input[0].len() - 1).try_into()?,
@@ -84,7 +90,10 @@ fn dijkstra(input: &Vec<Vec<i64>>, part2: bool) -> anyhow::Result<i64> {
             break;
         }
         visited.insert(current);
-        let nodes = neighbors(&input, current, part2).zip(OFFSETS);
+        let nodes = neighbors(input, current, part2)
+            .into_iter()
+            .zip(OFFSETS)
+            .collect_vec();
 
         nodes
             .iter()
@@ -103,23 +112,208 @@ fn dijkstra(input: &Vec<Vec<i64>>, part2: bool) -> anyhow::Result<i64> {
     Ok(get(&weights, goal).unwrap())
 }
 
+/// Risks with the path highlighted on 24-bit ANSI colors
+fn overlay(grid: &impl RiskMap, path: &[usize]) -> String {
+    let mut on_path = vec![false; grid.len()];
+    path.iter().for_each(|&p| on_path[p] = true);
+    let mut out = String::new();
+    for (index, on_path) in on_path.into_iter().enumerate() {
+        let risk = grid.risk(index);
+        if on_path {
+            out += &format!("\x1b[1;38;2;255;255;255;48;2;200;40;40m{risk}\x1b[0m");
+        } else {
+            out += &format!("\x1b[38;2;110;110;110m{risk}\x1b[0m");
+        }
+        if (index + 1) % grid.width() == 0 {
+            out.push('\n');
+        }
+    }
+    out
+}
+
+fn report(grid: &impl RiskMap, route: &Route, show_path: bool, show_overlay: bool) {
+    if show_path {
+        let cells = route.path.iter().map(|&p| grid.coordinates(p));
+        println!("{}", cells.map(|(x, y)| format!("({x},{y})")).join(" "));
+    }
+    if show_overlay {
+        print!("{}", overlay(grid, &route.path));
+    }
+}
+
+/// Times the reference implementation, Dijkstra, A* and Dial on the same grid. The reference only
+/// knows the puzzle's 5x5 tiling, `part2` selects it.
+fn compare(input: &[Vec<i64>], grid: &impl RiskMap, part2: Option<bool>) -> anyhow::Result<()> {
+    if let Some(part2) = part2 {
+        let start = Instant::now();
+        let cost = dijkstra(input, part2)?;
+        println!("reference: cost {cost} in {:?}", start.elapsed());
+    }
+    for (name, heuristic) in [("dijkstra", false), ("a*", true)] {
+        let start = Instant::now();
+        let route = search::a_star(grid, 0, grid.len() - 1, heuristic);
+        let elapsed = start.elapsed();
+        if let Some(route) = route {
+            println!(
+                "{name}: cost {} in {elapsed:?}, {} cells expanded",
+                route.cost, route.expanded
+            );
+        }
+    }
+    let start = Instant::now();
+    if let Some(route) = search::dial(grid, 0, grid.len() - 1) {
+        println!(
+            "dial: cost {} in {:?}, {} cells expanded",
+            route.cost,
+            start.elapsed(),
+            route.expanded
+        );
+    }
+    Ok(())
+}
+
 fn main() -> anyhow::Result<()> {
-    let file = std::env::args().nth(1).ok_or(AocError::NoInputFile)?;
+    let mut args = std::env::args().skip(1);
+    let file = args.next().ok_or(AocError::NoInputFile)?;
     let input = std::fs::read_to_string(file).context("Failed to read input file")?;
-    let input = input
-        .lines()
-        .map(|l| {
-            l.chars()
-                .flat_map(|c| format!("{}", c).parse::<i64>())
-                .collect_vec()
-        })
-        .collect_vec();
-
-    let part1 = dijkstra(&input, false)?;
+
+    // Optional: --path prints the coordinates of the routes, --overlay draws them on the map,
+    // --compare times the search strategies.
+    // Part 2 tiling: --factor N or NxM (default 5), --increment N per tile (default 1),
+    // --wrap MIN-MAX range the risks wrap around in (default 1-9)
+    let mut factor = (5, 5);
+    let mut increment = Increment::default();
+    let mut show_path = false;
+    let mut show_overlay = false;
+    let mut show_comparison = false;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--factor" => {
+                let value = args.next().unwrap_or_default();
+                let (x, y) = value.split_once('x').unwrap_or((&value, &value));
+                factor = x
+                    .parse()
+                    .ok()
+                    .zip(y.parse().ok())
+                    .filter(|&(x, y)| x > 0 && y > 0)
+                    .ok_or_else(|| AocError::ParseError(format!("invalid factor \"{value}\"")))?;
+            }
+            "--increment" => {
+                increment.increment = args
+                    .next()
+                    .and_then(|t| t.parse().ok())
+                    .ok_or_else(|| AocError::ParseError("--increment needs a number".to_owned()))?
+            }
+            "--wrap" => {
+                let value = args.next().unwrap_or_default();
+                (increment.min, increment.max) = value
+                    .split_once('-')
+                    .and_then(|(min, max)| Some((min.parse().ok()?, max.parse().ok()?)))
+                    .filter(|&(min, max)| min <= max)
+                    .ok_or_else(|| AocError::ParseError(format!("invalid range \"{value}\"")))?;
+            }
+            "--path" => show_path = true,
+            "--overlay" => show_overlay = true,
+            "--compare" => show_comparison = true,
+            _ => return Err(AocError::ParseError(format!("unknown argument {arg}")).into()),
+        }
+    }
+
+    let grid: Grid = input.parse()?;
+    let expanded = Tiled::new(&grid, factor, increment)?;
+    if show_comparison {
+        let input = input
+            .lines()
+            .map(|l| {
+                l.chars()
+                    .flat_map(|c| format!("{}", c).parse::<i64>())
+                    .collect_vec()
+            })
+            .collect_vec();
+        compare(&input, &grid, Some(false))?;
+        let puzzle_tiling = factor == (5, 5) && increment == Increment::default();
+        compare(&input, &expanded, puzzle_tiling.then_some(true))?;
+    }
+
+    let route = search::dial(&grid, 0, grid.len() - 1)
+        .ok_or_else(|| AocError::ParseError("no route".to_owned()))?;
+    report(&grid, &route, show_path, show_overlay);
+    let part1 = route.cost;
     dbg!(&part1);
 
-    let part2 = dijkstra(&input, true)?;
+    let route = search::dial(&expanded, 0, expanded.len() - 1)
+        .ok_or_else(|| AocError::ParseError("no route".to_owned()))?;
+    report(&expanded, &route, show_path, show_overlay);
+    let part2 = route.cost;
     dbg!(&part2);
 
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn finds_routes() {
+        let grid: Grid = "1163751742\n1381373672\n2136511328\n3694931569\n7463417111\n\
+                          1319128137\n1359912421\n3125421639\n1293138521\n2311944581"
+            .parse()
+            .unwrap();
+        let expanded = Tiled::new(&grid, (5, 5), Increment::default()).unwrap();
+        for heuristic in [false, true] {
+            let route = search::a_star(&grid, 0, grid.len() - 1, heuristic).unwrap();
+            assert_eq!(route.cost, 40);
+            let entered = route.path[1..].iter().map(|&p| grid.risk[p]).sum::<u32>();
+            assert_eq!(entered, 40);
+            assert!(route
+                .path
+                .windows(2)
+                .all(|w| grid.distance(w[0], w[1]) == 1));
+
+            let route = search::a_star(&expanded, 0, expanded.len() - 1, heuristic).unwrap();
+            assert_eq!(route.cost, 315);
+        }
+        let route = search::dial(&expanded, 0, expanded.len() - 1).unwrap();
+        assert_eq!(route.cost, 315);
+        assert_eq!(
+            route.path[1..]
+                .iter()
+                .map(|&p| expanded.risk(p))
+                .sum::<u32>(),
+            315
+        );
+    }
+
+    #[test]
+    fn tiles_lazily() {
+        let grid: Grid = "8".parse().unwrap();
+        let tiled = Tiled::new(&grid, (5, 1), Increment::default()).unwrap();
+        assert_eq!((0..5).map(|i| tiled.risk(i)).collect_vec(), [8, 9, 1, 2, 3]);
+        assert_eq!(tiled.risk_range(), (1, 9));
+
+        let increment = Increment {
+            increment: 3,
+            min: 0,
+            max: 9,
+        };
+        let tiled = Tiled::new(&grid, (2, 3), increment).unwrap();
+        assert_eq!((tiled.width(), tiled.height()), (2, 3));
+        assert_eq!(
+            (0..6).map(|i| tiled.risk(i)).collect_vec(),
+            [8, 1, 1, 4, 4, 7]
+        );
+        assert_eq!(tiled.risk_range(), (1, 8));
+
+        // Risks outside the wrap range would change even in the first tile
+        let grid: Grid = "80".parse().unwrap();
+        assert!(Tiled::new(&grid, (5, 5), Increment::default()).is_err());
+        let tiled = Tiled::new(&grid, (2, 1), increment).unwrap();
+        assert_eq!((0..4).map(|i| tiled.risk(i)).collect_vec(), [8, 0, 1, 3]);
+        let wrap = Increment {
+            min: 3,
+            ..Increment::default()
+        };
+        assert!(Tiled::new(&"12".parse().unwrap(), (2, 2), wrap).is_err());
+    }
+}
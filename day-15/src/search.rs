@@ -0,0 +1,114 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use crate::grid::RiskMap;
+
+/// Cheapest way from one cell to another
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Route {
+    /// Sum of the risks of all cells entered, so without the start
+    pub cost: u32,
+    /// Cells from start to goal
+    pub path: Vec<usize>,
+    /// Cells taken from the queue until the goal was reached
+    pub expanded: usize,
+}
+
+fn reconstruct(came_from: &[usize], start: usize, goal: usize) -> Vec<usize> {
+    let mut path = vec![goal];
+    let mut cell = goal;
+    while cell != start {
+        cell = came_from[cell];
+        path.push(cell);
+    }
+    path.reverse();
+    path
+}
+
+/// A* over flat cell indices. With `heuristic` the remaining cost is estimated as the Manhattan
+/// distance times the smallest risk, which never overestimates; without it this is Dijkstra.
+///
+/// Cells are pushed again whenever their cost improves and stale queue entries are skipped
+/// when popped, which is cheaper than a queue that supports decreasing keys.
+pub fn a_star(grid: &impl RiskMap, start: usize, goal: usize, heuristic: bool) -> Option<Route> {
+    let min_risk = if heuristic { grid.risk_range().0 } else { 0 };
+    let estimate = |index| grid.distance(index, goal) as u32 * min_risk;
+
+    let mut cost = vec![u32::MAX; grid.len()];
+    let mut came_from = vec![usize::MAX; grid.len()];
+    let mut queue = BinaryHeap::from([Reverse((estimate(start), start))]);
+    cost[start] = 0;
+    let mut expanded = 0;
+
+    while let Some(Reverse((priority, current))) = queue.pop() {
+        if priority > cost[current] + estimate(current) {
+            continue;
+        }
+        expanded += 1;
+        if current == goal {
+            return Some(Route {
+                cost: cost[goal],
+                path: reconstruct(&came_from, start, goal),
+                expanded,
+            });
+        }
+        for neighbor in grid.neighbors(current) {
+            let new_cost = cost[current] + grid.risk(neighbor);
+            if new_cost < cost[neighbor] {
+                cost[neighbor] = new_cost;
+                came_from[neighbor] = current;
+                queue.push(Reverse((new_cost + estimate(neighbor), neighbor)));
+            }
+        }
+    }
+    None
+}
+
+/// Dijkstra with Dial's bucket queue.
+///
+/// All tentative costs in the queue lie within the largest risk of the cost being expanded, so
+/// `max risk + 1` buckets used circularly, indexed by cost modulo their number, keep cells
+/// sorted without any comparisons. Stale entries are skipped like in [`a_star`].
+///
+/// 5000x5000 (1000x1000 input, puzzle tiling): reference 44.9s, heap Dijkstra 7.2s, A* 9.2s,
+/// Dial 4.2s
+pub fn dial(grid: &impl RiskMap, start: usize, goal: usize) -> Option<Route> {
+    let buckets = grid.risk_range().1 as usize + 1;
+    let mut queue = vec![Vec::new(); buckets];
+    let mut cost = vec![u32::MAX; grid.len()];
+    let mut came_from = vec![usize::MAX; grid.len()];
+    queue[0].push(start);
+    cost[start] = 0;
+    let mut queued = 1;
+    let mut expanded = 0;
+
+    let mut current_cost = 0;
+    while queued > 0 {
+        let bucket = current_cost as usize % buckets;
+        while let Some(current) = queue[bucket].pop() {
+            queued -= 1;
+            if cost[current] != current_cost {
+                continue;
+            }
+            expanded += 1;
+            if current == goal {
+                return Some(Route {
+                    cost: current_cost,
+                    path: reconstruct(&came_from, start, goal),
+                    expanded,
+                });
+            }
+            for neighbor in grid.neighbors(current) {
+                let new_cost = current_cost + grid.risk(neighbor);
+                if new_cost < cost[neighbor] {
+                    cost[neighbor] = new_cost;
+                    came_from[neighbor] = current;
+                    queue[new_cost as usize % buckets].push(neighbor);
+                    queued += 1;
+                }
+            }
+        }
+        current_cost += 1;
+    }
+    None
+}